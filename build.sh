@@ -4,6 +4,7 @@
 cd ./contracts/data_id
 #cd ./contracts/split_policy
 #cd ./contracts/accountant
+#cd ./contracts/registry
 
 cargo build --release --target=wasm32-unknown-unknown
 #disable "layer1" feature to build layer2 contract
@@ -5,6 +5,7 @@ use core::option::Option;
 use ontio_std::abi::EventBuilder;
 use ostd::abi::Error::IrregularData;
 use ostd::abi::{Decoder, Encoder, Error, Sink, Source};
+use ostd::contract::wasm;
 use ostd::prelude::*;
 use ostd::runtime::{check_witness, contract_delete, contract_migrate};
 
@@ -85,6 +86,38 @@ impl Fee {
             count: 0,
         }
     }
+
+    /// unit price when buying `n` shares, the tier with the largest `min_n` not above `n` wins,
+    /// `count` is used if no tier matches
+    pub fn unit_price(&self, tiers: &[PriceTier], n: U128) -> u64 {
+        tiers
+            .iter()
+            .filter(|tier| tier.min_n as U128 <= n)
+            .max_by_key(|tier| tier.min_n)
+            .map(|tier| tier.count)
+            .unwrap_or(self.count)
+    }
+
+    /// whether the two fees are paid with the same token
+    pub fn same_currency(&self, other: &Fee) -> bool {
+        match (self.contract_type, other.contract_type) {
+            (TokenType::ONT, TokenType::ONT) | (TokenType::ONG, TokenType::ONG) => true,
+            (TokenType::OEP4, TokenType::OEP4) => self.contract_addr == other.contract_addr,
+            _ => false,
+        }
+    }
+
+    /// total amount of `n` shares
+    pub fn total_amount(&self, tiers: &[PriceTier], n: U128) -> U128 {
+        n.checked_mul(self.unit_price(tiers, n) as U128).unwrap()
+    }
+}
+
+/// volume pricing, buying at least `min_n` shares in one order costs `count` per share
+#[derive(Encoder, Decoder, Clone)]
+pub struct PriceTier {
+    pub min_n: u64,
+    pub count: u64,
 }
 
 #[derive(Clone, Copy)]
@@ -112,6 +145,37 @@ impl<'a> Decoder<'a> for TokenType {
     }
 }
 
+/// call the split policy contract to pay `amt` in the currency of `fee` from `from` to the receivers of `key`
+///
+/// the split policy contracts deployed before items accepted other currencies only have `transferWithdraw`,
+/// which pays in the registered currency, so it is used whenever `fee` is paid in the registered currency
+pub fn split_transfer_withdraw(
+    split_contract: &Address,
+    from: &Address,
+    key: &[u8],
+    amt: U128,
+    fee: &Fee,
+) -> Option<Vec<u8>> {
+    let res = wasm::call_contract(split_contract, ("getRegisterParam", (key,)))
+        .expect("getRegisterParam failed");
+    let mut source = Source::new(res.as_slice());
+    let (_, contract_type, contract_addr): (Vec<(Address, u32, bool)>, TokenType, Option<Address>) =
+        source.read().unwrap();
+    let registered = Fee {
+        contract_addr: contract_addr.unwrap_or_else(|| fee.contract_addr.clone()),
+        contract_type,
+        count: 0,
+    };
+    if registered.same_currency(fee) {
+        wasm::call_contract(split_contract, ("transferWithdraw", (from, key, amt)))
+    } else {
+        wasm::call_contract(
+            split_contract,
+            ("transferWithdrawFee", (from, key, amt, fee)),
+        )
+    }
+}
+
 pub struct ContractCommon {
     admin: Address,
 }
@@ -38,3 +38,28 @@ fn test_fee() {
     let fee2: Fee = source.read().unwrap();
     assert_eq!(fee.count, fee2.count);
 }
+
+#[test]
+fn test_price_tier() {
+    let fee = Fee {
+        contract_addr: Address::repeat_byte(1),
+        contract_type: TokenType::ONG,
+        count: 10,
+    };
+    let tiers = vec![
+        PriceTier {
+            min_n: 10,
+            count: 8,
+        },
+        PriceTier {
+            min_n: 100,
+            count: 5,
+        },
+    ];
+    assert_eq!(fee.unit_price(&tiers, 1), 10);
+    assert_eq!(fee.unit_price(&tiers, 10), 8);
+    assert_eq!(fee.unit_price(&tiers, 99), 8);
+    assert_eq!(fee.unit_price(&tiers, 100), 5);
+    assert_eq!(fee.total_amount(&tiers, 200), 1000);
+    assert_eq!(fee.total_amount(&[], 3), 30);
+}
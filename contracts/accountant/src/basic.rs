@@ -22,3 +22,13 @@ impl SettleInfo {
         }
     }
 }
+
+#[derive(Encoder, Decoder)]
+pub struct EscrowInfo {
+    pub buyer: Address,
+    pub payer: Address,
+    pub seller: Address,
+    pub release_time: u64,
+    pub disputed: bool,
+    pub marketplace: Address, // the marketplace contract notified when the fee is released or refunded
+}
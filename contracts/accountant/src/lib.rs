@@ -2,11 +2,11 @@
 #![feature(proc_macro_hygiene)]
 extern crate alloc;
 extern crate ontio_std as ostd;
-use ostd::abi::{Decoder, Encoder, Sink, Source};
+use ostd::abi::{Decoder, Encoder, EventBuilder, Sink, Source};
 use ostd::contract::{ong, ont, wasm};
 use ostd::database;
 use ostd::prelude::*;
-use ostd::runtime::{address, check_witness, input, ret};
+use ostd::runtime::{address, caller, check_witness, input, ret, storage_read, timestamp};
 use ostd::types::{Address, U128};
 
 mod utils;
@@ -15,7 +15,7 @@ use utils::*;
 mod basic;
 use basic::*;
 extern crate common;
-use common::{Fee, OrderId, TokenType, CONTRACT_COMMON};
+use common::{split_transfer_withdraw, Fee, OrderId, PriceTier, TokenType, CONTRACT_COMMON};
 
 #[cfg(test)]
 mod test;
@@ -73,9 +73,34 @@ fn transfer_amount(
     split_contract_address: &Address,
     fee: Fee,
     n: U128,
+) -> bool {
+    transfer_amount_with_tiers(
+        order_id_bytes,
+        buyer_acc,
+        split_contract_address,
+        fee,
+        n,
+        vec![],
+    )
+}
+
+/// same as `transfer_amount`, the cost of one share is looked up in the volume price schedule of the item
+///
+/// `tiers` is the volume price schedule of the item, the cost of one share is `fee.count` if it is empty
+fn transfer_amount_with_tiers(
+    order_id_bytes: &[u8],
+    buyer_acc: &Address,
+    split_contract_address: &Address,
+    fee: Fee,
+    n: U128,
+    tiers: Vec<PriceTier>,
 ) -> bool {
     assert!(check_witness(buyer_acc));
-    let amt = n.checked_mul(fee.count as U128).unwrap();
+    let balance_key = utils::generate_balance_key(order_id_bytes);
+    let amount_key = utils::generate_amount_key(order_id_bytes);
+    assert!(storage_read(balance_key.as_slice()).is_none());
+    assert!(storage_read(amount_key.as_slice()).is_none());
+    let amt = fee.total_amount(tiers.as_slice(), n);
     let self_addr = address();
     assert!(transfer(
         buyer_acc,
@@ -91,22 +116,203 @@ fn transfer_amount(
         fee,
         n,
     };
-    database::put(utils::generate_balance_key(order_id_bytes), info);
+    database::put(balance_key, info);
+    database::put(amount_key, amt);
+    true
+}
+
+/// trust or distrust a marketplace contract to escrow the fee of its orders, need the contract admin signature
+fn set_trusted_caller(contract: &Address, trusted: bool) -> bool {
+    assert!(check_witness(CONTRACT_COMMON.admin()));
+    let key = utils::generate_trusted_caller_key(contract);
+    if trusted {
+        database::put(key, true);
+    } else {
+        database::delete(key);
+    }
+    EventBuilder::new()
+        .string("setTrustedCaller")
+        .address(contract)
+        .bool(trusted)
+        .notify();
+    true
+}
+
+fn is_trusted_caller(contract: &Address) -> bool {
+    database::get::<_, bool>(utils::generate_trusted_caller_key(contract)).unwrap_or(false)
+}
+
+/// transfer fee to the contract and hold it until the dispute window of the order ends
+///
+/// only a trusted marketplace contract can invoke this method, see [`set_trusted_caller`](fn.set_trusted_caller.html)
+///
+/// `order_id_bytes` is the serialization result of OrderId
+///
+/// `payer_acc` is the address who pay the fee, need the address signature
+///
+/// `buyer_acc` is the buyer address who can open a dispute, verified by the marketplace, which may have only
+/// the buyer's off-chain signature of the purchase
+///
+/// `seller_acc` is the seller address whose charging model is used when the fee is released
+///
+/// `dispute_window` is the number of seconds during which the buyer can open a dispute
+///
+/// the calling marketplace contract is notified by its `updateOrderStatus` method when the fee is released or refunded
+fn transfer_amount_escrow(
+    order_id_bytes: &[u8],
+    payer_acc: &Address,
+    buyer_acc: &Address,
+    seller_acc: &Address,
+    split_contract_address: &Address,
+    fee: Fee,
+    n: U128,
+    tiers: Vec<PriceTier>,
+    dispute_window: u64,
+) -> bool {
+    assert!(is_trusted_caller(&caller()));
+    let escrow_key = utils::generate_escrow_key(order_id_bytes);
+    assert!(storage_read(escrow_key.as_slice()).is_none());
+    assert!(transfer_amount_with_tiers(
+        order_id_bytes,
+        payer_acc,
+        split_contract_address,
+        fee,
+        n,
+        tiers
+    ));
+    let release_time = timestamp().checked_add(dispute_window).unwrap();
+    let escrow = EscrowInfo {
+        buyer: buyer_acc.clone(),
+        payer: payer_acc.clone(),
+        seller: seller_acc.clone(),
+        release_time,
+        disputed: false,
+        marketplace: caller(),
+    };
+    database::put(escrow_key, escrow);
+    EventBuilder::new()
+        .string("escrow")
+        .bytearray(order_id_bytes)
+        .address(buyer_acc)
+        .number(release_time as U128)
+        .notify();
+    true
+}
+
+/// query escrow info by order id
+fn get_escrow_info(order_id: &[u8]) -> Option<EscrowInfo> {
+    database::get(utils::generate_escrow_key(order_id))
+}
+
+/// buyer opens a dispute before the dispute window ends, the fee is frozen until mp arbitrates
+///
+/// `buyer_acc` is the buyer address of the order, need the address signature
+fn dispute(buyer_acc: &Address, order_id: &[u8]) -> bool {
+    assert!(check_witness(buyer_acc));
+    let mut escrow = get_escrow_info(order_id).expect("not escrowed order");
+    assert_eq!(&escrow.buyer, buyer_acc);
+    assert!(!escrow.disputed);
+    assert!(timestamp() < escrow.release_time);
+    escrow.disputed = true;
+    database::put(utils::generate_escrow_key(order_id), escrow);
+    EventBuilder::new()
+        .string("dispute")
+        .bytearray(order_id)
+        .address(buyer_acc)
+        .notify();
+    true
+}
+
+/// mp arbitrates a disputed order, need mp signature
+///
+/// `refund` is true to return the fee to the payer, false to release it to the seller.
+/// a refund only returns the fee, the dtokens generated for the buyer are kept by the buyer
+/// and the sold count of the item is not changed by the marketplace
+fn arbitrate(order_id: &[u8], refund: bool) -> bool {
+    assert!(check_witness(&get_mp_account()));
+    let escrow = get_escrow_info(order_id).expect("not escrowed order");
+    assert!(escrow.disputed);
+    database::delete(utils::generate_escrow_key(order_id));
+    if refund {
+        let fee = get_settle_info(order_id).fee;
+        assert!(transfer(
+            &address(),
+            &escrow.payer,
+            get_settle_amount(order_id),
+            &fee.contract_type,
+            Some(fee.contract_addr)
+        ));
+        database::delete(utils::generate_balance_key(order_id));
+        database::delete(utils::generate_amount_key(order_id));
+    } else {
+        assert!(settle_inner(&escrow.seller, order_id));
+    }
+    update_order_status(&escrow.marketplace, order_id, refund);
+    EventBuilder::new()
+        .string("arbitrate")
+        .bytearray(order_id)
+        .bool(refund)
+        .notify();
     true
 }
 
+/// release the fee of an escrowed order to the seller after the dispute window ends, anyone can invoke this method
+fn release(order_id: &[u8]) -> bool {
+    let escrow = get_escrow_info(order_id).expect("not escrowed order");
+    assert!(!escrow.disputed);
+    assert!(timestamp() >= escrow.release_time);
+    database::delete(utils::generate_escrow_key(order_id));
+    assert!(settle_inner(&escrow.seller, order_id));
+    update_order_status(&escrow.marketplace, order_id, false);
+    EventBuilder::new()
+        .string("release")
+        .bytearray(order_id)
+        .notify();
+    true
+}
+
+// inner method
+//
+// notify the marketplace contract that the escrowed fee of the order is released or refunded
+fn update_order_status(marketplace: &Address, order_id: &[u8], refunded: bool) {
+    let res = wasm::call_contract(marketplace, ("updateOrderStatus", (order_id, refunded)));
+    if let Some(rr) = res {
+        let mut source = Source::new(rr.as_slice());
+        let r: bool = source.read().unwrap();
+        assert!(r);
+    } else {
+        panic!("call marketplace contract failed")
+    }
+}
+
 /// query settle info by order id
 fn get_settle_info(order_id: &[u8]) -> SettleInfo {
     database::get(utils::generate_balance_key(order_id)).unwrap_or(SettleInfo::default())
 }
 
-/// expense settlement, first transfer fee to mp, second invoke "transferWithdraw" method of split contract
+/// query the total amount paid for an order
+///
+/// the orders transferred before the amount was recorded are paid `n` times the cost of one share
+fn get_settle_amount(order_id: &[u8]) -> U128 {
+    database::get(utils::generate_amount_key(order_id)).unwrap_or_else(|| {
+        let info = get_settle_info(order_id);
+        info.n.checked_mul(info.fee.count as U128).unwrap()
+    })
+}
+
+/// expense settlement, first transfer fee to mp, second invoke "transferWithdraw" method of split contract,
+/// or "transferWithdrawFee" if the fee is not paid in the registered currency of the split policy
 ///
 /// `seller_acc` is the seller address, need the address signature
 ///
 /// `order_id` is the serialization result of OrderId
 fn settle(seller_acc: &Address, order_id: &[u8]) -> bool {
     assert!(check_witness(seller_acc));
+    assert!(get_escrow_info(order_id).is_none());
+    settle_inner(seller_acc, order_id)
+}
+
+fn settle_inner(seller_acc: &Address, order_id: &[u8]) -> bool {
     let self_addr = address();
     let mp = get_mp_account();
     let info = get_settle_info(order_id);
@@ -114,7 +320,7 @@ fn settle(seller_acc: &Address, order_id: &[u8]) -> bool {
     //1. mp
     let fee_split = get_fee_split_model(seller_acc);
     let fee = info.fee;
-    let total = info.n.checked_mul(fee.count as U128).unwrap();
+    let total = get_settle_amount(order_id);
     let mp_fee = total.checked_mul(fee_split.weight as U128).unwrap();
     let mp_amt = mp_fee.checked_div(MAX_PERCENTAGE).unwrap();
     if mp_amt != 0 {
@@ -123,15 +329,18 @@ fn settle(seller_acc: &Address, order_id: &[u8]) -> bool {
             &mp,
             mp_amt,
             &fee.contract_type,
-            Some(fee.contract_addr)
+            Some(fee.contract_addr.clone())
         ));
     }
     //2.split
     let seller_amt = total.checked_sub(mp_amt).unwrap();
     let oi = OrderId::from_bytes(order_id);
-    let res = wasm::call_contract(
+    let res = split_transfer_withdraw(
         &info.split_contract_addr,
-        ("transferWithdraw", (&self_addr, oi.item_id, seller_amt)),
+        &self_addr,
+        oi.item_id.as_slice(),
+        seller_amt,
+        &fee,
     );
     if let Some(rr) = res {
         let mut source = Source::new(rr.as_slice());
@@ -141,6 +350,7 @@ fn settle(seller_acc: &Address, order_id: &[u8]) -> bool {
         panic!("call split contract failed")
     }
     database::delete(utils::generate_balance_key(order_id));
+    database::delete(utils::generate_amount_key(order_id));
     true
 }
 
@@ -200,10 +410,73 @@ pub fn invoke() {
                 n,
             ));
         }
+        b"transferAmountWithTiers" => {
+            let (order_id_bytes, buyer_acc, seller_acc, fee, n, tiers) = source.read().unwrap();
+            sink.write(transfer_amount_with_tiers(
+                order_id_bytes,
+                buyer_acc,
+                seller_acc,
+                fee,
+                n,
+                tiers,
+            ));
+        }
+        b"transferAmountEscrow" => {
+            let (
+                order_id_bytes,
+                payer_acc,
+                buyer_acc,
+                seller_acc,
+                split_acc,
+                fee,
+                n,
+                tiers,
+                window,
+            ) = source.read().unwrap();
+            sink.write(transfer_amount_escrow(
+                order_id_bytes,
+                payer_acc,
+                buyer_acc,
+                seller_acc,
+                split_acc,
+                fee,
+                n,
+                tiers,
+                window,
+            ));
+        }
+        b"setTrustedCaller" => {
+            let (contract, trusted) = source.read().unwrap();
+            sink.write(set_trusted_caller(contract, trusted));
+        }
+        b"isTrustedCaller" => {
+            let contract = source.read().unwrap();
+            sink.write(is_trusted_caller(contract));
+        }
+        b"getEscrowInfo" => {
+            let order_id_bytes = source.read().unwrap();
+            sink.write(get_escrow_info(order_id_bytes));
+        }
+        b"dispute" => {
+            let (buyer_acc, order_id) = source.read().unwrap();
+            sink.write(dispute(buyer_acc, order_id));
+        }
+        b"arbitrate" => {
+            let (order_id, refund) = source.read().unwrap();
+            sink.write(arbitrate(order_id, refund));
+        }
+        b"release" => {
+            let order_id = source.read().unwrap();
+            sink.write(release(order_id));
+        }
         b"balanceOf" => {
             let order_id_bytes = source.read().unwrap();
             sink.write(get_settle_info(order_id_bytes));
         }
+        b"getSettleAmount" => {
+            let order_id_bytes = source.read().unwrap();
+            sink.write(get_settle_amount(order_id_bytes));
+        }
         b"settle" => {
             let (seller_acc, order_id) = source.read().unwrap();
             sink.write(settle(seller_acc, order_id));
@@ -36,7 +36,7 @@ fn test() {
         if _addr == &ONG_CONTRACT_ADDRESS {
             mock_ong_contract(_data, &mut ong_balance_map)
         } else {
-            Some(vec![1u8])
+            mock_contract(_data)
         }
     };
     build.on_contract_call(call_contract);
@@ -66,6 +66,50 @@ fn test() {
     let seller_balance = get_settle_info(oi.to_bytes().as_slice());
 }
 
+#[test]
+fn transfer_amount_with_tiers_test() {
+    let build = build_runtime();
+    build.on_contract_call(|_addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        mock_contract(data)
+    });
+    let buyer = Address::repeat_byte(3);
+    let split = Address::repeat_byte(5);
+    let fee = Fee {
+        contract_addr: Address::repeat_byte(6),
+        contract_type: TokenType::OEP4,
+        count: 10,
+    };
+    build.witness(&[buyer.clone()]);
+    let tiers = vec![PriceTier { min_n: 5, count: 8 }];
+    assert!(transfer_amount_with_tiers(
+        b"order1",
+        &buyer,
+        &split,
+        fee.clone(),
+        5,
+        tiers
+    ));
+    assert_eq!(get_settle_amount(b"order1"), 40);
+    assert_eq!(get_settle_info(b"order1").n, 5);
+
+    assert!(transfer_amount(b"order2", &buyer, &split, fee, 5));
+    assert_eq!(get_settle_amount(b"order2"), 50);
+}
+
+// split policies are registered in ONG, other methods return true
+fn mock_contract(data: &[u8]) -> Option<Vec<u8>> {
+    let mut sink = Sink::new(12);
+    let mut source = Source::new(data);
+    match source.read::<&[u8]>() {
+        Ok(b"getRegisterParam") => {
+            let receivers: Vec<(Address, u32, bool)> = vec![];
+            sink.write((receivers, TokenType::ONG, None::<Address>));
+        }
+        _ => sink.write(true),
+    }
+    Some(sink.bytes().to_vec())
+}
+
 fn mock_ong_contract(
     _data: &[u8],
     ong_balance_map: &mut BTreeMap<Address, U128>,
@@ -94,3 +138,226 @@ fn mock_ong_contract(
     }
     return Some(sink.bytes().to_vec());
 }
+
+#[test]
+fn escrow_release_test() {
+    let build = escrow_order(b"order1");
+    build.timestamp(199);
+    assert!(get_escrow_info(b"order1").is_some());
+    build.timestamp(200);
+    assert!(release(b"order1"));
+    assert!(get_escrow_info(b"order1").is_none());
+    assert_eq!(get_settle_amount(b"order1"), 0);
+}
+
+#[test]
+#[should_panic]
+fn release_in_dispute_window_test() {
+    let build = escrow_order(b"order1");
+    build.timestamp(199);
+    release(b"order1");
+}
+
+#[test]
+#[should_panic]
+fn settle_escrowed_order_test() {
+    let build = escrow_order(b"order1");
+    let seller = Address::repeat_byte(2);
+    build.witness(&[seller.clone()]);
+    build.timestamp(200);
+    settle(&seller, b"order1");
+}
+
+#[test]
+fn dispute_refund_test() {
+    let build = escrow_order(b"order1");
+    let buyer = Address::repeat_byte(3);
+    build.witness(&[buyer.clone()]);
+    assert!(dispute(&buyer, b"order1"));
+    assert!(get_escrow_info(b"order1").unwrap().disputed);
+    build.witness(&[*CONTRACT_COMMON.admin()]);
+    assert!(arbitrate(b"order1", true));
+    assert!(get_escrow_info(b"order1").is_none());
+    assert_eq!(get_settle_amount(b"order1"), 0);
+}
+
+#[test]
+fn dispute_release_test() {
+    let build = escrow_order(b"order1");
+    let buyer = Address::repeat_byte(3);
+    build.witness(&[buyer.clone()]);
+    assert!(dispute(&buyer, b"order1"));
+    build.witness(&[*CONTRACT_COMMON.admin()]);
+    assert!(arbitrate(b"order1", false));
+    assert!(get_escrow_info(b"order1").is_none());
+    assert_eq!(get_settle_amount(b"order1"), 0);
+}
+
+#[test]
+#[should_panic]
+fn release_disputed_order_test() {
+    let build = escrow_order(b"order1");
+    let buyer = Address::repeat_byte(3);
+    build.witness(&[buyer.clone()]);
+    assert!(dispute(&buyer, b"order1"));
+    build.timestamp(200);
+    release(b"order1");
+}
+
+#[test]
+#[should_panic]
+fn dispute_after_window_test() {
+    let build = escrow_order(b"order1");
+    let buyer = Address::repeat_byte(3);
+    build.witness(&[buyer.clone()]);
+    build.timestamp(200);
+    dispute(&buyer, b"order1");
+}
+
+#[test]
+#[should_panic]
+fn arbitrate_undisputed_order_test() {
+    let build = escrow_order(b"order1");
+    build.witness(&[*CONTRACT_COMMON.admin()]);
+    arbitrate(b"order1", true);
+}
+
+#[test]
+#[should_panic]
+fn escrow_order_twice_test() {
+    let build = escrow_order(b"order1");
+    let payer = Address::repeat_byte(7);
+    let buyer = Address::repeat_byte(3);
+    build.witness(&[buyer.clone(), payer.clone()]);
+    transfer_amount_escrow(
+        b"order1",
+        &payer,
+        &buyer,
+        &Address::repeat_byte(2),
+        &Address::repeat_byte(5),
+        escrow_fee(),
+        1,
+        vec![],
+        100,
+    );
+}
+
+#[test]
+#[should_panic]
+fn escrow_by_untrusted_caller_test() {
+    let build = build_runtime();
+    build.on_contract_call(|_addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        mock_contract(data)
+    });
+    let payer = Address::repeat_byte(7);
+    let buyer = Address::repeat_byte(3);
+    build.caller(&buyer);
+    build.witness(&[buyer.clone(), payer.clone()]);
+    transfer_amount_escrow(
+        b"order1",
+        &payer,
+        &buyer,
+        &Address::repeat_byte(2),
+        &Address::repeat_byte(5),
+        escrow_fee(),
+        1,
+        vec![],
+        100,
+    );
+}
+
+#[test]
+fn escrow_without_buyer_signature_test() {
+    let build = build_runtime();
+    build.on_contract_call(|_addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        mock_contract(data)
+    });
+    let payer = Address::repeat_byte(7);
+    let buyer = Address::repeat_byte(3);
+    let marketplace = Address::repeat_byte(8);
+    build.witness(&[*CONTRACT_COMMON.admin()]);
+    assert!(set_trusted_caller(&marketplace, true));
+    // a purchase relayed by the payer
+    build.caller(&marketplace);
+    build.witness(&[payer.clone()]);
+    assert!(transfer_amount_escrow(
+        b"order1",
+        &payer,
+        &buyer,
+        &Address::repeat_byte(2),
+        &Address::repeat_byte(5),
+        escrow_fee(),
+        1,
+        vec![],
+        100
+    ));
+    assert_eq!(get_escrow_info(b"order1").unwrap().buyer, buyer);
+}
+
+#[test]
+fn escrow_marketplace_test() {
+    let build = escrow_order(b"order1");
+    assert_eq!(
+        get_escrow_info(b"order1").unwrap().marketplace,
+        Address::repeat_byte(8)
+    );
+    build.witness(&[*CONTRACT_COMMON.admin()]);
+    assert!(set_trusted_caller(&Address::repeat_byte(8), false));
+    assert!(!is_trusted_caller(&Address::repeat_byte(8)));
+}
+
+#[test]
+#[should_panic]
+fn transfer_amount_twice_test() {
+    let build = build_runtime();
+    build.on_contract_call(|_addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        mock_contract(data)
+    });
+    let buyer = Address::repeat_byte(3);
+    let split = Address::repeat_byte(5);
+    build.witness(&[buyer.clone()]);
+    assert!(transfer_amount(b"order1", &buyer, &split, escrow_fee(), 1));
+    transfer_amount(b"order1", &buyer, &split, escrow_fee(), 1);
+}
+
+fn escrow_fee() -> Fee {
+    Fee {
+        contract_addr: Address::repeat_byte(6),
+        contract_type: TokenType::OEP4,
+        count: 10,
+    }
+}
+
+// escrow 2 shares of `order_id` paid by a payer for the buyer Address::repeat_byte(3) at time 100
+// through the trusted marketplace Address::repeat_byte(8),
+// the dispute window ends at time 200
+fn escrow_order(order_id: &[u8]) -> ostd::mock::RuntimeHandle {
+    let build = build_runtime();
+    build.on_contract_call(|_addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        mock_contract(data)
+    });
+    build.address(&Address::repeat_byte(4));
+    build.timestamp(100);
+    let payer = Address::repeat_byte(7);
+    let buyer = Address::repeat_byte(3);
+    let seller = Address::repeat_byte(2);
+    let marketplace = Address::repeat_byte(8);
+    build.witness(&[*CONTRACT_COMMON.admin()]);
+    assert!(set_trusted_caller(&marketplace, true));
+    build.caller(&marketplace);
+    build.witness(&[buyer.clone(), payer.clone()]);
+    assert!(transfer_amount_escrow(
+        order_id,
+        &payer,
+        &buyer,
+        &seller,
+        &Address::repeat_byte(5),
+        escrow_fee(),
+        2,
+        vec![],
+        100
+    ));
+    assert_eq!(get_settle_amount(order_id), 20);
+    assert_eq!(get_escrow_info(order_id).unwrap().release_time, 200);
+    build
+}
@@ -3,6 +3,9 @@ use super::*;
 const KEY_FEE_SPLIT_MODEL: &[u8] = b"01";
 const KEY_BALANCE: &[u8] = b"02";
 pub const KEY_MP: &[u8] = b"03";
+const KEY_ESCROW: &[u8] = b"04";
+const KEY_AMOUNT: &[u8] = b"05";
+const KEY_TRUSTED_CALLER: &[u8] = b"06";
 
 pub fn generate_fee_split_model_key(account: &Address) -> Vec<u8> {
     [KEY_FEE_SPLIT_MODEL, account.as_ref()].concat()
@@ -10,3 +13,12 @@ pub fn generate_fee_split_model_key(account: &Address) -> Vec<u8> {
 pub fn generate_balance_key(order_id: &[u8]) -> Vec<u8> {
     [KEY_BALANCE, order_id].concat()
 }
+pub fn generate_escrow_key(order_id: &[u8]) -> Vec<u8> {
+    [KEY_ESCROW, order_id].concat()
+}
+pub fn generate_amount_key(order_id: &[u8]) -> Vec<u8> {
+    [KEY_AMOUNT, order_id].concat()
+}
+pub fn generate_trusted_caller_key(contract: &Address) -> Vec<u8> {
+    [KEY_TRUSTED_CALLER, contract.as_ref()].concat()
+}
@@ -31,6 +31,7 @@ const PRE_AUTHORIZED: &[u8] = b"07";
 const PRE_TOKEN_ID: &[u8] = b"08";
 const PRE_TEMPLATE_ID: &[u8] = b"09";
 const PRE_AGENT: &[u8] = b"10";
+const PRE_VALID_UNTIL: &[u8] = b"11";
 
 #[cfg(feature = "layer1")]
 const PRE_LAYER2: &[u8] = b"L";
@@ -110,6 +111,74 @@ pub fn generate_dtoken_multi(
         .collect::<Vec<Vec<u8>>>()
 }
 
+/// generate dtoken which can only be used before `valid_until`
+///
+/// the marketplace contract invokes this method to sell subscription items
+///
+/// `valid_until` is the timestamp after which the token can not be used
+pub fn generate_dtoken_with_validity(
+    acc: &Address,
+    token_template_id: &[u8],
+    n: U128,
+    valid_until: u64,
+) -> Vec<u8> {
+    let token_id = generate_dtoken(acc, token_template_id, n);
+    database::put(get_key(PRE_VALID_UNTIL, token_id.as_slice()), valid_until);
+    EventBuilder::new()
+        .string("setTokenValidity")
+        .bytearray(token_id.as_slice())
+        .number(valid_until as U128)
+        .notify();
+    token_id
+}
+
+pub fn generate_dtoken_with_validity_multi(
+    acc: &Address,
+    token_template_ids: &[Vec<u8>],
+    n: U128,
+    valid_until: u64,
+) -> Vec<Vec<u8>> {
+    token_template_ids
+        .iter()
+        .map(|x| generate_dtoken_with_validity(acc, x, n, valid_until))
+        .collect::<Vec<Vec<u8>>>()
+}
+
+/// extend the validity of a time-bound token by `duration`
+///
+/// the caller must be the creator or an authorized address of the token template,
+/// an expired token is extended from now
+pub fn extend_token_validity(token_template_id: &[u8], token_id: &[u8], duration: u64) -> bool {
+    let caller = runtime::caller();
+    assert!(is_valid_addr(&[&caller], token_template_id));
+    assert_eq!(
+        get_template_id_by_token_id(token_id).as_slice(),
+        token_template_id
+    );
+    let key = get_key(PRE_VALID_UNTIL, token_id);
+    let valid_until = database::get::<_, u64>(key.as_slice()).expect("token has no validity");
+    let now = runtime::timestamp();
+    let start = if valid_until > now { valid_until } else { now };
+    let valid_until = start.checked_add(duration).unwrap();
+    database::put(key, valid_until);
+    EventBuilder::new()
+        .string("setTokenValidity")
+        .bytearray(token_id)
+        .number(valid_until as U128)
+        .notify();
+    true
+}
+
+/// query the timestamp after which the token can not be used, 0 means the token never expires
+pub fn get_token_validity(token_id: &[u8]) -> u64 {
+    database::get(get_key(PRE_VALID_UNTIL, token_id)).unwrap_or(0)
+}
+
+fn is_token_expired(token_id: &[u8]) -> bool {
+    let valid_until = get_token_validity(token_id);
+    valid_until != 0 && runtime::timestamp() > valid_until
+}
+
 pub fn get_token_template(token_template_id: &[u8]) -> Option<TokenTemplate> {
     let info: Option<TokenTemplateInfo> = database::get(get_key(PRE_TT, token_template_id));
     info.map(|data| data.token_template)
@@ -267,6 +336,7 @@ fn update_next_tt_id(new_id: U128) {
 /// `n` represents the number of consuming token
 pub fn use_token(account: &Address, token_id: &[u8], n: U128) -> bool {
     assert!(check_witness(account));
+    assert!(!is_token_expired(token_id));
     let ba = oep8::balance_of(account, token_id);
     assert!(ba >= n);
     oep8::destroy_token(account, token_id, n);
@@ -291,6 +361,7 @@ pub fn delete_token(account: &Address, token_id: &[u8]) -> bool {
     database::delete(key.as_slice());
     let key = get_key(PRE_AUTHORIZED, template_id.as_slice());
     database::delete(key.as_slice());
+    database::delete(get_key(PRE_VALID_UNTIL, token_id));
     EventBuilder::new()
         .string("deleteToken")
         .address(account)
@@ -312,6 +383,7 @@ pub fn delete_token(account: &Address, token_id: &[u8]) -> bool {
 /// `n` represents the number of consuming token
 pub fn use_token_by_agent(account: &Address, agent: &Address, token_id: &[u8], n: U128) -> bool {
     assert!(check_witness(agent));
+    assert!(!is_token_expired(token_id));
     let ba = oep8::balance_of(account, token_id);
     assert!(ba >= n);
     let mut sink = Sink::new(64);
@@ -639,6 +711,29 @@ fn layer_action(action: &[u8], sink: &mut Sink, source: &mut Source) -> bool {
                 n,
             ));
         }
+        b"generateDTokenWithValidity" => {
+            let (account, token_template_id, n, valid_until) = source.read().unwrap();
+            sink.write(generate_dtoken_with_validity(
+                account,
+                token_template_id,
+                n,
+                valid_until,
+            ));
+        }
+        b"generateDTokenWithValidityMulti" => {
+            let (account, token_template_ids, n, valid_until): (&Address, Vec<Vec<u8>>, U128, u64) =
+                source.read().unwrap();
+            sink.write(generate_dtoken_with_validity_multi(
+                account,
+                token_template_ids.as_slice(),
+                n,
+                valid_until,
+            ));
+        }
+        b"extendTokenValidity" => {
+            let (token_template_id, token_id, duration) = source.read().unwrap();
+            sink.write(extend_token_validity(token_template_id, token_id, duration));
+        }
         b"deleteToken" => {
             let (account, token_id) = source.read().unwrap();
             sink.write(delete_token(account, token_id));
@@ -680,6 +775,10 @@ pub fn invoke() {
                 );
             }
             //*********************jwtToken method********************
+            b"getTokenValidity" => {
+                let token_id = source.read().unwrap();
+                sink.write(get_token_validity(token_id));
+            }
             b"getAgentBalance" => {
                 let (owner, agent, token_id) = source.read().unwrap();
                 sink.write(get_agent_balance(owner, agent, token_id));
@@ -175,3 +175,56 @@ fn serialize_templates(templates: &[TokenTemplate]) -> Vec<u8> {
     sink.write(templates);
     sink.bytes().to_vec()
 }
+
+#[test]
+fn token_validity_test() {
+    let handle = build_runtime();
+    let account = Address::repeat_byte(1);
+    handle.witness(&[account.clone()]);
+    handle.timestamp(100);
+    let token_id = generate_time_bound_token(&account, 10, 200);
+    assert_eq!(get_token_validity(token_id.as_slice()), 200);
+    handle.timestamp(200);
+    assert!(use_token(&account, token_id.as_slice(), 1));
+    assert_eq!(oep8::balance_of(&account, token_id.as_slice()), 9);
+}
+
+#[test]
+#[should_panic]
+fn use_expired_token_test() {
+    let handle = build_runtime();
+    let account = Address::repeat_byte(1);
+    handle.witness(&[account.clone()]);
+    handle.timestamp(100);
+    let token_id = generate_time_bound_token(&account, 10, 200);
+    handle.timestamp(201);
+    use_token(&account, token_id.as_slice(), 1);
+}
+
+#[test]
+#[should_panic]
+fn use_expired_token_by_agent_test() {
+    let handle = build_runtime();
+    let account = Address::repeat_byte(1);
+    let agent = Address::repeat_byte(2);
+    handle.witness(&[account.clone()]);
+    handle.timestamp(100);
+    let token_id = generate_time_bound_token(&account, 10, 200);
+    assert!(set_token_agents(
+        &account,
+        token_id.as_slice(),
+        vec![agent.clone()],
+        vec![5]
+    ));
+    handle.timestamp(201);
+    handle.witness(&[agent.clone()]);
+    use_token_by_agent(&account, &agent, token_id.as_slice(), 1);
+}
+
+// create a token template and generate `n` tokens of it valid until `valid_until` for the creator,
+// need the creator signature
+fn generate_time_bound_token(creator: &Address, n: U128, valid_until: u64) -> Vec<u8> {
+    let tt = TokenTemplate::new(b"name".to_vec(), b"symbol".to_vec(), None, vec![], vec![]);
+    assert!(create_token_template(creator, tt));
+    generate_dtoken_with_validity(creator, b"0", n, valid_until)
+}
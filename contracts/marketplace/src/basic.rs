@@ -1,7 +1,21 @@
-use super::ostd::abi::{Decoder, Encoder, Source};
+use super::ostd::abi::Error::IrregularData;
+use super::ostd::abi::{Decoder, Encoder, Error, Sink, Source};
 use super::ostd::prelude::*;
-use super::ostd::types::{Address, H256};
-use common::Fee;
+use super::ostd::types::{Address, H256, U128};
+use common::{Fee, PriceTier};
+
+const SIGNING_DOMAIN: &[u8] = b"ddxf-marketplace";
+
+// the message signed off-chain is the signing domain and the message type followed by the serialized payload,
+// the payload carries the marketplace address, so that a signature is valid for one type of message in one
+// marketplace only
+fn signed_message<T: Encoder>(msg_type: &[u8], payload: &T) -> Vec<u8> {
+    let mut sink = Sink::new(64);
+    sink.write(SIGNING_DOMAIN);
+    sink.write(msg_type);
+    sink.write(payload);
+    sink.bytes().to_vec()
+}
 
 #[derive(Clone, Encoder, Decoder)]
 pub struct ResourceDDO {
@@ -19,14 +33,13 @@ impl ResourceDDO {
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        use super::ostd::abi::Sink;
         let mut sink = Sink::new(64);
         sink.write(self);
         sink.bytes().to_vec()
     }
 }
 
-#[derive(Encoder, Decoder, Clone)]
+#[derive(Clone)]
 pub struct SellerItemInfo {
     pub item: DTokenItem,
     pub resource_ddo: ResourceDDO,
@@ -38,25 +51,583 @@ impl SellerItemInfo {
     }
 }
 
-#[derive(Clone, Encoder, Decoder)]
+// the item fields added after the first version are written after the resource ddo,
+// so that the items stored by the first version are still decoded
+impl Encoder for SellerItemInfo {
+    fn encode(&self, sink: &mut Sink) {
+        self.item.encode_base(sink);
+        sink.write(&self.resource_ddo);
+        self.item.encode_ext(sink);
+    }
+}
+
+impl<'a> Decoder<'a> for SellerItemInfo {
+    fn decode(source: &mut Source<'a>) -> Result<Self, Error> {
+        let mut item = DTokenItem::decode_base(source)?;
+        let resource_ddo = source.read()?;
+        item.decode_ext(source)?;
+        Ok(SellerItemInfo { item, resource_ddo })
+    }
+}
+
+/// the fields after `token_template_ids` were added after the first version of the item,
+/// they get their defaults when they are missing from the encoded item
+#[derive(Clone)]
 pub struct DTokenItem {
     pub fee: Fee,
     pub expired_date: u64,
     pub stocks: u64,
     pub sold: u64,
     pub token_template_ids: Vec<Vec<u8>>,
+    pub start_date: u64,
+    pub sale_mode: SaleMode,
+    pub price_tiers: Vec<PriceTier>, // can be empty
+    pub dispute_window: u64,         // seconds, 0 means the fee is paid without escrow
+    pub royalty: u16,                // royalty of secondary sales, in the range of 0 to 10000
+    pub buyer_access: BuyerAccess,
+    pub credential: Option<CredentialRequirement>, // can be empty
+    pub buyer_limit: BuyerLimit,
+    pub alt_fees: Vec<Fee>, // other accepted currencies and their unit prices, can be empty
+}
+
+impl Encoder for DTokenItem {
+    fn encode(&self, sink: &mut Sink) {
+        self.encode_base(sink);
+        self.encode_ext(sink);
+    }
+}
+
+impl<'a> Decoder<'a> for DTokenItem {
+    fn decode(source: &mut Source<'a>) -> Result<Self, Error> {
+        let mut item = DTokenItem::decode_base(source)?;
+        item.decode_ext(source)?;
+        Ok(item)
+    }
 }
 
 impl DTokenItem {
+    /// encode the fields of the first version of the item
+    pub fn encode_base(&self, sink: &mut Sink) {
+        sink.write(&self.fee);
+        sink.write(self.expired_date);
+        sink.write(self.stocks);
+        sink.write(self.sold);
+        sink.write(&self.token_template_ids);
+    }
+
+    /// decode the fields of the first version of the item, the other fields get their defaults
+    pub fn decode_base(source: &mut Source) -> Result<Self, Error> {
+        Ok(DTokenItem {
+            fee: source.read()?,
+            expired_date: source.read()?,
+            stocks: source.read()?,
+            sold: source.read()?,
+            token_template_ids: source.read()?,
+            start_date: 0,
+            sale_mode: SaleMode::Fixed,
+            price_tiers: vec![],
+            dispute_window: 0,
+            royalty: 0,
+            buyer_access: BuyerAccess::Open,
+            credential: None,
+            buyer_limit: BuyerLimit::default(),
+            alt_fees: vec![],
+        })
+    }
+
+    /// encode the fields added after the first version of the item
+    pub fn encode_ext(&self, sink: &mut Sink) {
+        sink.write(self.start_date);
+        sink.write(&self.sale_mode);
+        sink.write(&self.price_tiers);
+        sink.write(self.dispute_window);
+        sink.write(self.royalty);
+        sink.write(&self.buyer_access);
+        sink.write(&self.credential);
+        sink.write(&self.buyer_limit);
+        sink.write(&self.alt_fees);
+    }
+
+    /// decode the fields added after the first version of the item,
+    /// they keep their defaults if `source` ends before them
+    pub fn decode_ext(&mut self, source: &mut Source) -> Result<(), Error> {
+        self.start_date = match source.read() {
+            Ok(start_date) => start_date,
+            Err(_) => return Ok(()),
+        };
+        self.sale_mode = source.read()?;
+        self.price_tiers = source.read()?;
+        self.dispute_window = source.read()?;
+        self.royalty = source.read()?;
+        self.buyer_access = source.read()?;
+        self.credential = source.read()?;
+        self.buyer_limit = source.read()?;
+        self.alt_fees = source.read()?;
+        Ok(())
+    }
+
     pub fn from_bytes(data: &[u8]) -> Self {
         let mut source = Source::new(data);
         source.read().unwrap()
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        use super::ostd::abi::Sink;
         let mut sink = Sink::new(64);
         sink.write(self);
         sink.bytes().to_vec()
     }
+
+    /// fee and price tiers to charge at `now`
+    pub fn pricing(&self, now: u64) -> (Fee, Vec<PriceTier>) {
+        match &self.sale_mode {
+            SaleMode::Dutch(param) => {
+                let mut fee = self.fee.clone();
+                fee.count = param.price_at(now);
+                (fee, vec![])
+            }
+            _ => (self.fee.clone(), self.price_tiers.clone()),
+        }
+    }
+
+    /// fee and price tiers to charge at `now` in the currency chosen by `fee_index`,
+    /// 0 means `fee`, i means `alt_fees[i - 1]` which has no price tiers
+    pub fn pricing_in(&self, now: u64, fee_index: usize) -> (Fee, Vec<PriceTier>) {
+        if fee_index == 0 {
+            return self.pricing(now);
+        }
+        let fee = self
+            .alt_fees
+            .get(fee_index - 1)
+            .expect("currency not accepted");
+        (fee.clone(), vec![])
+    }
+}
+
+/// purchase limits of every buyer, 0 means unlimited
+#[derive(Clone, Encoder, Decoder)]
+pub struct BuyerLimit {
+    pub max_per_buyer: u64,
+    pub max_per_window: u64,
+    pub window: u64, // seconds
+}
+
+impl BuyerLimit {
+    pub fn default() -> Self {
+        BuyerLimit {
+            max_per_buyer: 0,
+            max_per_window: 0,
+            window: 0,
+        }
+    }
+}
+
+#[derive(Encoder, Decoder)]
+pub struct BuyerPurchase {
+    pub total: u64,
+    pub window_start: u64,
+    pub window_count: u64,
+}
+
+impl BuyerPurchase {
+    pub fn default() -> Self {
+        BuyerPurchase {
+            total: 0,
+            window_start: 0,
+            window_count: 0,
+        }
+    }
+}
+
+/// the buyer's ONT ID must hold a claim issued by a trusted issuer,
+/// the issuer records the claim as an attribute of its own ONT ID, keyed by the buyer's ONT ID
+#[derive(Clone, Encoder, Decoder)]
+pub struct CredentialRequirement {
+    pub issuer: Vec<u8>, // ONT ID of the trusted issuer
+    pub claim: Vec<u8>,
+}
+
+/// who can buy an item, the access list is managed by the item manager
+#[derive(Clone, Copy)]
+pub enum BuyerAccess {
+    Open = 0,
+    Allowlist = 1,
+    Denylist = 2,
+}
+
+impl Encoder for BuyerAccess {
+    fn encode(&self, sink: &mut Sink) {
+        sink.write(*self as u8);
+    }
+}
+
+impl<'a> Decoder<'a> for BuyerAccess {
+    fn decode(source: &mut Source<'a>) -> Result<Self, Error> {
+        let ty: u8 = source.read()?;
+        match ty {
+            0u8 => Ok(BuyerAccess::Open),
+            1u8 => Ok(BuyerAccess::Allowlist),
+            2u8 => Ok(BuyerAccess::Denylist),
+            _ => Err(IrregularData),
+        }
+    }
+}
+
+/// how an item is sold, `Fixed` uses `fee.count` as the unit price
+#[derive(Clone)]
+pub enum SaleMode {
+    Fixed,
+    Auction(AuctionParam),
+    Dutch(DutchParam),
+    Subscription(SubscriptionParam),
+    Oracle(OracleParam),
+    Presale(PresaleParam),
+}
+
+impl Encoder for SaleMode {
+    fn encode(&self, sink: &mut Sink) {
+        match self {
+            SaleMode::Fixed => sink.write(0u8),
+            SaleMode::Auction(param) => {
+                sink.write(1u8);
+                sink.write(param);
+            }
+            SaleMode::Dutch(param) => {
+                sink.write(2u8);
+                sink.write(param);
+            }
+            SaleMode::Subscription(param) => {
+                sink.write(3u8);
+                sink.write(param);
+            }
+            SaleMode::Oracle(param) => {
+                sink.write(4u8);
+                sink.write(param);
+            }
+            SaleMode::Presale(param) => {
+                sink.write(5u8);
+                sink.write(param);
+            }
+        }
+    }
+}
+
+impl<'a> Decoder<'a> for SaleMode {
+    fn decode(source: &mut Source<'a>) -> Result<Self, Error> {
+        let ty: u8 = source.read()?;
+        match ty {
+            0u8 => Ok(SaleMode::Fixed),
+            1u8 => Ok(SaleMode::Auction(source.read()?)),
+            2u8 => Ok(SaleMode::Dutch(source.read()?)),
+            3u8 => Ok(SaleMode::Subscription(source.read()?)),
+            4u8 => Ok(SaleMode::Oracle(source.read()?)),
+            5u8 => Ok(SaleMode::Presale(source.read()?)),
+            _ => Err(IrregularData),
+        }
+    }
+}
+
+/// english auction, the whole remaining stock is sold as one lot to the highest bidder
+#[derive(Clone, Encoder, Decoder)]
+pub struct AuctionParam {
+    pub reserve_price: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub min_increment: u64,
+}
+
+/// descending price sale, the unit price decays linearly from `start_price` to `floor_price`
+/// between `start_time` and `end_time`, and stays at `floor_price` afterwards
+#[derive(Clone, Encoder, Decoder)]
+pub struct DutchParam {
+    pub start_price: u64,
+    pub floor_price: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+impl DutchParam {
+    pub fn price_at(&self, now: u64) -> u64 {
+        if now <= self.start_time {
+            return self.start_price;
+        }
+        if now >= self.end_time {
+            return self.floor_price;
+        }
+        let elapsed = (now - self.start_time) as u128;
+        let duration = (self.end_time - self.start_time) as u128;
+        let drop = (self.start_price - self.floor_price) as u128 * elapsed / duration;
+        self.start_price - drop as u64
+    }
+}
+
+/// subscription, `fee.count` is the price of one period and the dtokens expire when the paid periods end
+#[derive(Clone, Encoder, Decoder)]
+pub struct SubscriptionParam {
+    pub period: u64, // seconds
+}
+
+/// priced in a reference unit such as USD cents, converted to the payment currency by the oracle contract
+/// when buying, `fee.count` and price tiers are ignored
+#[derive(Clone, Encoder, Decoder)]
+pub struct OracleParam {
+    pub ref_price: u64,
+}
+
+/// threshold pre-sale, the payments are escrowed in the marketplace contract until `deadline`,
+/// the sale is finalized if at least `min_sold` dtokens are committed, otherwise the buyers are refunded
+#[derive(Clone, Encoder, Decoder)]
+pub struct PresaleParam {
+    pub min_sold: u64,
+    pub deadline: u64,
+}
+
+/// escrowed payments of a pre-sale item
+#[derive(Clone, Encoder, Decoder)]
+pub struct PresaleState {
+    pub total: U128,  // total amount escrowed
+    pub pledges: u64, // number of pledges not claimed yet
+    pub finalized: bool,
+}
+
+impl PresaleState {
+    pub fn default() -> Self {
+        PresaleState {
+            total: 0,
+            pledges: 0,
+            finalized: false,
+        }
+    }
+}
+
+/// dtokens committed by a buyer in a pre-sale
+#[derive(Clone, Encoder, Decoder)]
+pub struct Pledge {
+    pub payer: Address,
+    pub n: U128,
+    pub amount: U128,
+}
+
+#[derive(Clone, Encoder, Decoder)]
+pub struct Bid {
+    pub bidder: Address,
+    pub payer: Address,
+    pub price: u64,
+}
+
+/// discount coupon signed off-chain by the item manager
+#[derive(Clone, Encoder, Decoder)]
+pub struct Coupon {
+    pub marketplace: Address, // the marketplace contract the coupon is signed for
+    pub coupon_id: Vec<u8>,
+    pub item_id: Vec<u8>,
+    pub discount: Discount,
+    pub max_redemptions: u64,
+    pub expired_date: u64,
+    pub buyer: Option<Address>, // can be empty, anyone can redeem the coupon
+}
+
+impl Coupon {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(self);
+        sink.bytes().to_vec()
+    }
+
+    /// the message signed off-chain
+    pub fn signed_message(&self) -> Vec<u8> {
+        signed_message(b"coupon", self)
+    }
+}
+
+/// publish permission signed by the marketplace admin off-chain
+#[derive(Clone, Encoder, Decoder)]
+pub struct Endorsement {
+    pub marketplace: Address, // the marketplace contract the endorsement is signed for
+    pub item_id: Vec<u8>,
+    pub item_hash: H256, // sha256 of the serialized ResourceDDO, DTokenItem and split policy param
+    pub expired_date: u64,
+    pub nonce: u64,
+}
+
+impl Endorsement {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(self);
+        sink.bytes().to_vec()
+    }
+
+    /// the message signed off-chain
+    pub fn signed_message(&self) -> Vec<u8> {
+        signed_message(b"endorsement", self)
+    }
+}
+
+/// complete item signed by the seller and the marketplace admin off-chain, published by the first purchase
+#[derive(Clone, Encoder, Decoder)]
+pub struct Listing {
+    pub marketplace: Address, // the marketplace contract the listing is signed for
+    pub item_id: Vec<u8>,
+    pub resource_ddo: ResourceDDO,
+    pub item: DTokenItem,
+    pub split_policy_param: Vec<u8>,
+    pub expired_date: u64,
+    pub nonce: u64, // the seller revokes the listing by `revoke_listing` with this nonce
+}
+
+impl Listing {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(self);
+        sink.bytes().to_vec()
+    }
+
+    /// the message signed off-chain
+    pub fn signed_message(&self) -> Vec<u8> {
+        signed_message(b"listing", self)
+    }
+}
+
+/// purchase signed by the buyer off-chain and submitted by a relayer
+#[derive(Clone, Encoder, Decoder)]
+pub struct PurchaseIntent {
+    pub marketplace: Address, // the marketplace contract the intent is signed for
+    pub item_id: Vec<u8>,
+    pub n: U128,
+    pub max_unit_price: U128,
+    pub buyer: Address,
+    pub nonce: u64,
+    pub deadline: u64,
+}
+
+impl PurchaseIntent {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(self);
+        sink.bytes().to_vec()
+    }
+
+    /// the message signed off-chain
+    pub fn signed_message(&self) -> Vec<u8> {
+        signed_message(b"purchaseIntent", self)
+    }
+}
+
+/// discount of the unit price, `Percent` is in the range of 0 to 100
+#[derive(Clone)]
+pub enum Discount {
+    Percent(u16),
+    Fixed(u64),
+}
+
+impl Discount {
+    pub fn apply(&self, unit_price: u64) -> u64 {
+        match self {
+            Discount::Percent(percent) => {
+                assert!(*percent <= 100);
+                let off = unit_price as u128 * *percent as u128 / 100;
+                unit_price - off as u64
+            }
+            Discount::Fixed(amount) => unit_price.saturating_sub(*amount),
+        }
+    }
+}
+
+impl Encoder for Discount {
+    fn encode(&self, sink: &mut Sink) {
+        match self {
+            Discount::Percent(percent) => {
+                sink.write(0u8);
+                sink.write(*percent);
+            }
+            Discount::Fixed(amount) => {
+                sink.write(1u8);
+                sink.write(*amount);
+            }
+        }
+    }
+}
+
+impl<'a> Decoder<'a> for Discount {
+    fn decode(source: &mut Source<'a>) -> Result<Self, Error> {
+        let ty: u8 = source.read()?;
+        match ty {
+            0u8 => Ok(Discount::Percent(source.read()?)),
+            1u8 => Ok(Discount::Fixed(source.read()?)),
+            _ => Err(IrregularData),
+        }
+    }
+}
+
+/// standing sell order of a reseller, the dtokens are escrowed in the marketplace contract
+#[derive(Clone, Encoder, Decoder)]
+pub struct SellOrder {
+    pub resource_id: Vec<u8>,
+    pub seller: Address,
+    pub dtoken_contract: Address,
+    pub token_id: Vec<u8>,
+    pub amount: U128, // remaining amount
+    pub price: Fee,   // `price.count` is the unit price
+    pub expired_date: u64,
+}
+
+/// several published items sold together at the bundle price
+#[derive(Clone, Encoder, Decoder)]
+pub struct Bundle {
+    pub resource_ids: Vec<Vec<u8>>, // one of every component item is sold in a bundle
+    pub fee: Fee,                   // `fee.count` is the price of one bundle
+    pub expired_date: u64,
+}
+
+/// receipt of one item bought by `checkout`
+#[derive(Encoder, Decoder)]
+pub struct CartReceipt {
+    pub resource_id: Vec<u8>,
+    pub n: U128,
+    pub amount: U128,
+    pub token_ids: Vec<Vec<u8>>,
+}
+
+/// state of an order
+#[derive(Clone, Copy, PartialEq)]
+pub enum OrderStatus {
+    Paid = 0,     // the fee is paid to the split policy
+    Escrowed = 1, // the fee is held by the accountant contract until it is settled
+    Released = 2, // the escrowed fee is released to the seller
+    Refunded = 3, // the escrowed fee is refunded to the payer
+}
+
+impl Encoder for OrderStatus {
+    fn encode(&self, sink: &mut Sink) {
+        sink.write(*self as u8);
+    }
+}
+
+impl<'a> Decoder<'a> for OrderStatus {
+    fn decode(source: &mut Source<'a>) -> Result<Self, Error> {
+        let ty: u8 = source.read()?;
+        match ty {
+            0u8 => Ok(OrderStatus::Paid),
+            1u8 => Ok(OrderStatus::Escrowed),
+            2u8 => Ok(OrderStatus::Released),
+            3u8 => Ok(OrderStatus::Refunded),
+            _ => Err(IrregularData),
+        }
+    }
+}
+
+/// purchase record of the marketplace, `order_id` is the serialized `OrderId` followed by the sequence of the order
+/// in its transaction, the sequence is omitted for the first order of a transaction
+#[derive(Clone, Encoder, Decoder)]
+pub struct Order {
+    pub order_id: Vec<u8>,
+    pub resource_id: Vec<u8>,
+    pub buyer: Address,
+    pub payer: Address,
+    pub n: U128,
+    pub fee: Fee,     // currency of the payment, `fee.count` is the unit price paid
+    pub amount: U128, // total amount paid
+    pub token_ids: Vec<Vec<u8>>,
+    pub status: OrderStatus,
+    pub timestamp: u64,
+    pub accountant: Option<Address>, // the accountant contract holding the fee of an escrowed order
 }
@@ -47,6 +47,77 @@ pub fn verify_auth(dtokens_contract_addr: &[Address], token_template_ids: &[Vec<
     }
 }
 
+/// assert that every token template has authorized this marketplace to generate its dtokens,
+/// the token templates are paired with the dtoken contracts the same way as `verify_auth`
+pub fn assert_authorized(dtokens_contract_addr: &[Address], token_template_ids: &[Vec<u8>]) {
+    let self_addr = address();
+    if !dtokens_contract_addr.is_empty() {
+        for i in 0..dtokens_contract_addr.len() {
+            let authorized = get_authorized_addr(
+                dtokens_contract_addr.get(i).unwrap(),
+                token_template_ids.get(i).unwrap(),
+            );
+            assert!(authorized.contains(&self_addr));
+        }
+    } else {
+        let dtoken = get_dtoken_contract();
+        for token_template_id in token_template_ids.iter() {
+            assert!(get_authorized_addr(&dtoken, token_template_id).contains(&self_addr));
+        }
+    }
+}
+
+pub fn get_authorized_addr(dtoken: &Address, token_template_id: &[u8]) -> Vec<Address> {
+    let res = wasm::call_contract(dtoken, ("getAuthorizedAddr", (token_template_id,)))
+        .expect("getAuthorizedAddr failed");
+    let mut source = Source::new(res.as_slice());
+    source.read().unwrap()
+}
+
+/// same as `verify_auth` for several items, every token template is verified and authorized only once,
+/// and the token templates of the same dtoken contract are handled by one call
+pub fn verify_auth_multi(items: &[(&[Address], &[Vec<u8>])]) {
+    let default_dtoken = get_dtoken_contract();
+    let mut groups: Vec<(Address, Vec<Vec<u8>>)> = vec![];
+    for (dtokens_contract_addr, token_template_ids) in items.iter() {
+        // paired the same way as `verify_auth`
+        let pairs: Vec<(&Address, &Vec<u8>)> = if dtokens_contract_addr.is_empty() {
+            token_template_ids
+                .iter()
+                .map(|token_template_id| (&default_dtoken, token_template_id))
+                .collect()
+        } else {
+            (0..dtokens_contract_addr.len())
+                .map(|i| {
+                    (
+                        dtokens_contract_addr.get(i).unwrap(),
+                        token_template_ids.get(i).unwrap(),
+                    )
+                })
+                .collect()
+        };
+        for (dtoken, token_template_id) in pairs {
+            match groups.iter_mut().find(|(addr, _)| addr == dtoken) {
+                Some((_, ids)) => {
+                    if !ids.contains(token_template_id) {
+                        ids.push(token_template_id.clone());
+                    }
+                }
+                None => groups.push((dtoken.clone(), vec![token_template_id.clone()])),
+            }
+        }
+    }
+    let self_addr = address();
+    for (dtoken, token_template_ids) in groups.iter() {
+        assert!(verify_creator_sig_multi(dtoken, token_template_ids));
+        assert!(auth_token_template_multi(
+            dtoken,
+            token_template_ids,
+            &[self_addr.clone()],
+        ));
+    }
+}
+
 pub fn auth_token_template_multi(
     dtoken: &Address,
     token_template_ids: &[Vec<u8>],
@@ -174,14 +245,13 @@ fn generate_dtoken_inner(
     token_template_id: &[u8],
     n: U128,
 ) -> Vec<u8> {
-    if let Some(res) = wasm::call_contract(
+    let res = wasm::call_contract(
         contract_address,
         ("generateDToken", (account, token_template_id, n)),
-    ) {
-        let mut source = Source::new(res.as_slice());
-        source.read().unwrap()
-    }
-    panic!("generateDToken failed")
+    )
+    .expect("generateDToken failed");
+    let mut source = Source::new(res.as_slice());
+    source.read().unwrap()
 }
 
 fn generate_dtoken_multi(
@@ -190,12 +260,145 @@ fn generate_dtoken_multi(
     token_template_ids: &[Vec<u8>],
     n: U128,
 ) -> Vec<Vec<u8>> {
-    if let Some(res) = wasm::call_contract(
+    let res = wasm::call_contract(
         contract_address,
         ("generateDTokenMulti", (account, token_template_ids, n)),
-    ) {
+    )
+    .expect("generate_dtoken_multi failed");
+    let mut source = Source::new(res.as_slice());
+    source.read().unwrap()
+}
+
+/// generate dtokens for `to` on behalf of this marketplace, which is authorized by the token templates,
+/// so that `to` does not need to sign the transaction
+pub fn generate_dtoken_for_other(
+    dtokens: &[Address],
+    token_template_ids: &[Vec<u8>],
+    to: &Address,
+    n: U128,
+) -> Vec<Vec<u8>> {
+    let self_addr = address();
+    let dtoken = get_dtoken_contract();
+    let mut token_ids = Vec::with_capacity(token_template_ids.len());
+    for (i, token_template_id) in token_template_ids.iter().enumerate() {
+        let contract_address = if dtokens.is_empty() {
+            &dtoken
+        } else {
+            &dtokens[i]
+        };
+        let res = wasm::call_contract(
+            contract_address,
+            (
+                "generateDTokenForOther",
+                (&self_addr, to, token_template_id, n),
+            ),
+        )
+        .expect("generateDTokenForOther failed");
+        let mut source = Source::new(res.as_slice());
+        token_ids.push(source.read().unwrap());
+    }
+    token_ids
+}
+
+pub fn generate_dtoken_with_validity(
+    dtokens: &[Address],
+    token_template_ids: &[Vec<u8>],
+    buyer_account: &Address,
+    n: U128,
+    valid_until: u64,
+) -> Vec<Vec<u8>> {
+    if dtokens.len() != 0 {
+        let l = dtokens.len();
+        let mut token_ids = Vec::with_capacity(l);
+        for i in 0..l {
+            let res = wasm::call_contract(
+                &dtokens[i],
+                (
+                    "generateDTokenWithValidity",
+                    (
+                        buyer_account,
+                        token_template_ids.get(i).unwrap(),
+                        n,
+                        valid_until,
+                    ),
+                ),
+            )
+            .expect("generateDTokenWithValidity failed");
+            let mut source = Source::new(res.as_slice());
+            token_ids.push(source.read().unwrap());
+        }
+        token_ids
+    } else {
+        let dtoken = get_dtoken_contract();
+        let res = wasm::call_contract(
+            &dtoken,
+            (
+                "generateDTokenWithValidityMulti",
+                (buyer_account, token_template_ids, n, valid_until),
+            ),
+        )
+        .expect("generateDTokenWithValidityMulti failed");
         let mut source = Source::new(res.as_slice());
         source.read().unwrap()
     }
-    panic!("generate_dtoken_multi failed")
+}
+
+pub fn extend_dtoken_validity(
+    dtokens: &[Address],
+    token_template_ids: &[Vec<u8>],
+    token_ids: &[Vec<u8>],
+    duration: u64,
+) {
+    assert_eq!(token_template_ids.len(), token_ids.len());
+    let dtoken = get_dtoken_contract();
+    for i in 0..token_ids.len() {
+        let contract_address = dtokens.get(i).unwrap_or(&dtoken);
+        verify_result(wasm::call_contract(
+            contract_address,
+            (
+                "extendTokenValidity",
+                (
+                    token_template_ids.get(i).unwrap(),
+                    token_ids.get(i).unwrap(),
+                    duration,
+                ),
+            ),
+        ));
+    }
+}
+
+pub fn get_template_id_by_token_id(contract_address: &Address, token_id: &[u8]) -> Vec<u8> {
+    let res = wasm::call_contract(contract_address, ("getTemplateIdByTokenId", (token_id,)))
+        .expect("getTemplateIdByTokenId failed");
+    let mut source = Source::new(res.as_slice());
+    source.read().unwrap()
+}
+
+pub fn transfer_oep8(
+    contract_address: &Address,
+    from: &Address,
+    to: &Address,
+    token_id: &[u8],
+    n: U128,
+) -> bool {
+    verify_result(wasm::call_contract(
+        contract_address,
+        ("transfer", (from, to, token_id, n)),
+    ));
+    true
+}
+
+pub fn transfer_from_oep8(
+    contract_address: &Address,
+    spender: &Address,
+    from: &Address,
+    to: &Address,
+    token_id: &[u8],
+    n: U128,
+) -> bool {
+    verify_result(wasm::call_contract(
+        contract_address,
+        ("transferFrom", (spender, from, to, token_id, n)),
+    ));
+    true
 }
@@ -15,19 +15,20 @@
 extern crate alloc;
 extern crate common;
 extern crate ontio_std as ostd;
+use core::convert::TryFrom;
 use ostd::abi::{EventBuilder, Sink, Source};
 use ostd::database;
 use ostd::prelude::*;
 use ostd::runtime;
-use ostd::types::{Address, U128};
+use ostd::types::{Address, H256, U128};
 mod basic;
 use basic::*;
 mod dtoken;
 mod split_policy;
 use common::*;
 use dtoken::*;
-use ostd::contract::wasm;
-use ostd::runtime::{check_witness, current_txhash};
+use ostd::contract::{ong, ont, ontid, wasm};
+use ostd::runtime::{address, check_witness, current_txhash};
 
 #[cfg(test)]
 mod test;
@@ -36,6 +37,36 @@ const KEY_SELLER_ITEM_INFO: &[u8] = b"01";
 const KEY_DTOKEN_CONTRACT: &[u8] = b"03";
 const KEY_SPLIT_POLICY_CONTRACT: &[u8] = b"04";
 const KEY_ADMIN: &[u8] = b"05";
+const KEY_AUCTION_BID: &[u8] = b"06";
+const KEY_SIGNING_KEY: &[u8] = b"07";
+const KEY_COUPON_REDEEMED: &[u8] = b"08";
+const KEY_SELL_ORDER: &[u8] = b"09";
+const KEY_SELL_ORDER_ID: &[u8] = b"10";
+
+const KEY_ITEM_PAUSED: &[u8] = b"11";
+const KEY_ACCESS_LIST: &[u8] = b"12";
+const KEY_BUYER_PURCHASE: &[u8] = b"13";
+const KEY_ORACLE_CONTRACT: &[u8] = b"14";
+const KEY_ORDER: &[u8] = b"15";
+const KEY_BUYER_ORDER: &[u8] = b"16";
+const KEY_ITEM_ORDER: &[u8] = b"17";
+const KEY_BUNDLE: &[u8] = b"18";
+const KEY_PRESALE: &[u8] = b"19";
+const KEY_PLEDGE: &[u8] = b"20";
+const KEY_INTENT_NONCE: &[u8] = b"21";
+const KEY_REGISTRY_CONTRACT: &[u8] = b"22";
+const KEY_ENDORSEMENT_NONCE: &[u8] = b"23";
+const KEY_LISTING_PUBLISHED: &[u8] = b"24";
+const KEY_ALL_ITEMS: &[u8] = b"25";
+const KEY_MANAGER_ITEMS: &[u8] = b"26";
+const KEY_TEMPLATE_ITEMS: &[u8] = b"27";
+const KEY_ITEM_INDEX: &[u8] = b"28";
+const KEY_ITEM_POSITION: &[u8] = b"29";
+const KEY_ORDER_SEQ: &[u8] = b"30";
+const KEY_LISTING_NONCE: &[u8] = b"31";
+
+const MAX_PERCENTAGE: U128 = 10000;
+const MAX_PAGE_SIZE: u64 = 100;
 
 //AbtTQJYKfQxq4UdygDsbLVjE8uRrJ2H3tP
 //AYnhakv7kC9R5ppw65JoE2rt6xDzCjCTvD
@@ -70,6 +101,35 @@ pub fn get_dtoken_contract() -> Address {
     database::get::<_, Address>(KEY_DTOKEN_CONTRACT).unwrap_or(DEFAULT_DTOKEN_CONTRACT)
 }
 
+/// set price oracle contract address, need admin signature
+///
+/// the oracle contract converts the reference price of items to the payment currency
+pub fn set_oracle_contract(new_addr: &Address) -> bool {
+    assert!(check_witness(CONTRACT_COMMON.admin()));
+    database::put(KEY_ORACLE_CONTRACT, new_addr);
+    true
+}
+
+/// query the price oracle contract address
+pub fn get_oracle_contract() -> Address {
+    database::get::<_, Address>(KEY_ORACLE_CONTRACT).expect("oracle contract not set")
+}
+
+// unit price in the currency of `fee` for an item priced `ref_price` in the reference unit
+//
+// the oracle returns the amount of the currency per reference unit and the update timestamp
+fn oracle_unit_price(fee: &Fee, ref_price: u64, now: u64, max_price_age: u64) -> u64 {
+    let res = wasm::call_contract(
+        &get_oracle_contract(),
+        ("getPrice", (&fee.contract_addr, fee.contract_type)),
+    )
+    .expect("call oracle contract failed");
+    let mut source = Source::new(res.as_slice());
+    let (rate, updated_at): (U128, u64) = source.read().unwrap();
+    assert!(updated_at <= now && now - updated_at <= max_price_age);
+    u64::try_from(rate.checked_mul(ref_price as U128).unwrap()).expect("price overflow")
+}
+
 /// set split contract address as the default split contract address,
 ///
 /// When there are multiple data owners, split contract is used to set the income distribution strategy.
@@ -101,6 +161,73 @@ fn get_admin() -> Address {
     database::get::<_, Address>(KEY_ADMIN).unwrap_or(*CONTRACT_COMMON.admin())
 }
 
+/// set the registry contract address, need the contract admin signature
+pub fn set_registry_contract(new_addr: &Address) -> bool {
+    assert!(check_witness(CONTRACT_COMMON.admin()));
+    database::put(KEY_REGISTRY_CONTRACT, new_addr);
+    true
+}
+
+/// query the registry contract address
+pub fn get_registry_contract() -> Address {
+    database::get::<_, Address>(KEY_REGISTRY_CONTRACT).expect("registry contract not set")
+}
+
+/// register this marketplace to the registry contract, need the marketplace admin signature
+///
+/// `endpoint` is where the browser can query the items of this marketplace
+///
+/// `fee_rate` is the fee charged by this marketplace, in the range of 0 to 10000
+pub fn register_marketplace(endpoint: &[u8], fee_rate: u16) -> bool {
+    let admin = get_admin();
+    assert!(check_witness(&admin));
+    verify_result(wasm::call_contract(
+        &get_registry_contract(),
+        ("register", (address(), admin, endpoint, fee_rate)),
+    ));
+    true
+}
+
+/// query whether this marketplace is registered in the registry contract
+pub fn is_registered() -> bool {
+    let res = wasm::call_contract(&get_registry_contract(), ("isRegistered", (address(),)))
+        .expect("isRegistered failed");
+    let mut source = Source::new(res.as_slice());
+    source.read().unwrap()
+}
+
+/// set the public key used to verify the messages signed off-chain by `account`, need account signature
+///
+/// the key is not checked against `account`, whoever controls `account` can set any key and the
+/// messages signed by it are accepted as signed by `account`. the key can be replaced at any time,
+/// a `setSigningKey` event is emitted on every change
+///
+/// `pub_key` is the public key of `account`
+pub fn set_signing_key(account: &Address, pub_key: &[u8]) -> bool {
+    assert!(check_witness(account));
+    database::put(utils::generate_signing_key_key(account), pub_key);
+    EventBuilder::new()
+        .string("setSigningKey")
+        .address(account)
+        .bytearray(pub_key)
+        .notify();
+    true
+}
+
+/// query the signing key of `account`, return empty bytes if not set
+pub fn get_signing_key(account: &Address) -> Vec<u8> {
+    database::get::<_, Vec<u8>>(utils::generate_signing_key_key(account)).unwrap_or_default()
+}
+
+// inner method
+//
+// panic if `sig` is not the signature of `msg` signed by the signing key of `signer`
+fn verify_signature(signer: &Address, msg: &[u8], sig: &[u8]) {
+    let pub_key = database::get::<_, Vec<u8>>(utils::generate_signing_key_key(signer))
+        .expect("signing key not set");
+    assert!(runtime::verify_signature(pub_key.as_slice(), msg, sig));
+}
+
 /// seller publish product, need seller signature
 ///
 /// `resource_id` used to mark the only commodity in the chain
@@ -111,6 +238,9 @@ fn get_admin() -> Address {
 ///
 /// `split_policy_param_bytes` is the result of RegisterParam struct serialization
 ///
+/// in the invoke arguments, the item fields added after `token_template_ids` follow `split_policy_param_bytes`
+/// and can be left out, so that the arguments of the first version are still accepted
+///
 /// # Example
 /// ```no_run
 /// use common::{Fee,TokenType};
@@ -163,6 +293,138 @@ pub fn dtoken_seller_publish_inner(
 ) -> bool {
     let admin = get_admin();
     assert!(runtime::check_witness(&resource_ddo.manager) && runtime::check_witness(&admin));
+    //verify token_template_id creator sig
+    // authorize mp address
+    verify_auth(
+        &resource_ddo.dtoken_contract_address,
+        item.token_template_ids.as_slice(),
+    );
+    publish_item(
+        item_id,
+        resource_ddo,
+        item,
+        split_policy_param_bytes,
+        is_publish,
+        None,
+    )
+}
+
+/// seller publish several products atomically, need the signatures of the marketplace admin and all the item managers
+///
+/// the token templates are verified and authorized once for every dtoken contract
+///
+/// `items` is array of `(resource_id, resource_ddo, item, split_policy_param)`, the same as the parameters of
+/// [`dtoken_seller_publish`](fn.dtoken_seller_publish.html), the resource ids can not contain duplicates
+pub fn dtoken_seller_publish_multi(
+    items: Vec<(Vec<u8>, ResourceDDO, DTokenItem, Vec<u8>)>,
+) -> bool {
+    assert_ne!(items.len(), 0);
+    assert!(runtime::check_witness(&get_admin()));
+    for (i, (resource_id, resource_ddo, _, _)) in items.iter().enumerate() {
+        assert!(runtime::check_witness(&resource_ddo.manager));
+        assert!(!items[..i].iter().any(|(id, _, _, _)| id == resource_id));
+    }
+    let auths: Vec<(&[Address], &[Vec<u8>])> = items
+        .iter()
+        .map(|(_, resource_ddo, item, _)| {
+            (
+                resource_ddo.dtoken_contract_address.as_slice(),
+                item.token_template_ids.as_slice(),
+            )
+        })
+        .collect();
+    verify_auth_multi(auths.as_slice());
+    for (resource_id, resource_ddo, item, split_policy_param) in items.into_iter() {
+        assert!(publish_item(
+            resource_id.as_slice(),
+            resource_ddo,
+            item,
+            split_policy_param.as_slice(),
+            true,
+            None
+        ));
+    }
+    true
+}
+
+/// seller publish product with an endorsement signed by the marketplace admin off-chain,
+/// only the seller needs to sign the transaction
+///
+/// the admin should have set his signing key by [`set_signing_key`](fn.set_signing_key.html),
+/// every nonce of the endorsement can be used only once
+///
+/// `resource_id`, `resource_ddo`, `item` and `split_policy_param` are the same as [`dtoken_seller_publish`](fn.dtoken_seller_publish.html)
+///
+/// `endorsement` is issued by the marketplace admin, `endorsement.item_hash` is the sha256 hash of the
+/// serialization result of `(resource_ddo, item, split_policy_param)`
+///
+/// `sig` is the signature of `endorsement.signed_message()`
+pub fn dtoken_seller_publish_endorsed(
+    resource_id: &[u8],
+    resource_ddo: ResourceDDO,
+    item: DTokenItem,
+    split_policy_param: &[u8],
+    endorsement: Endorsement,
+    sig: &[u8],
+) -> bool {
+    assert!(runtime::check_witness(&resource_ddo.manager));
+    assert_eq!(endorsement.marketplace, address());
+    assert_eq!(endorsement.item_id.as_slice(), resource_id);
+    assert!(runtime::timestamp() <= endorsement.expired_date);
+    let item_hash = endorsement_item_hash(&resource_ddo, &item, split_policy_param);
+    assert_eq!(endorsement.item_hash, item_hash);
+    verify_signature(&get_admin(), endorsement.signed_message().as_slice(), sig);
+    let key = utils::generate_endorsement_nonce_key(endorsement.nonce);
+    assert!(!database::get::<_, bool>(key.as_slice()).unwrap_or(false));
+    database::put(key, true);
+    verify_auth(
+        &resource_ddo.dtoken_contract_address,
+        item.token_template_ids.as_slice(),
+    );
+    publish_item(
+        resource_id,
+        resource_ddo,
+        item,
+        split_policy_param,
+        true,
+        None,
+    )
+}
+
+// inner method
+//
+// the hash of the item endorsed by the marketplace admin
+fn endorsement_item_hash(
+    resource_ddo: &ResourceDDO,
+    item: &DTokenItem,
+    split_policy_param: &[u8],
+) -> H256 {
+    let mut sink = Sink::new(64);
+    sink.write(resource_ddo);
+    sink.write(item);
+    sink.write(split_policy_param);
+    runtime::sha256(sink.bytes())
+}
+
+/// query whether the nonce of an endorsement has been used
+pub fn is_endorsement_nonce_used(nonce: u64) -> bool {
+    database::get::<_, bool>(utils::generate_endorsement_nonce_key(nonce)).unwrap_or(false)
+}
+
+// inner method
+//
+// the signatures of the manager and the marketplace and the auth of the token templates should be checked by the caller
+//
+// the split policy is registered with the signature of a receiver in the transaction, or on behalf of `split_signer`
+// whose off-chain signature of the split policy param has been verified by the caller
+fn publish_item(
+    item_id: &[u8],
+    resource_ddo: ResourceDDO,
+    mut item: DTokenItem,
+    split_policy_param_bytes: &[u8],
+    is_publish: bool,
+    split_signer: Option<&Address>,
+) -> bool {
     let resource =
         database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(item_id));
     if is_publish {
@@ -171,26 +433,96 @@ pub fn dtoken_seller_publish_inner(
         assert!(resource.is_some());
     }
     assert_ne!(item.token_template_ids.len(), 0);
+    if let SaleMode::Auction(param) = &item.sale_mode {
+        assert!(param.start_time < param.end_time);
+    }
+    if let SaleMode::Dutch(param) = &item.sale_mode {
+        assert!(param.start_time < param.end_time);
+        assert!(param.floor_price <= param.start_price);
+    }
+    if let SaleMode::Subscription(param) = &item.sale_mode {
+        assert_ne!(param.period, 0);
+    }
+    if let SaleMode::Presale(param) = &item.sale_mode {
+        assert!(param.min_sold <= item.stocks);
+        assert!(param.deadline <= item.expired_date);
+    }
+    if !is_publish {
+        // the escrowed bid must be settled before the item is changed
+        assert!(database::get::<_, Bid>(utils::generate_auction_bid_key(item_id)).is_none());
+        let presale = database::get::<_, PresaleState>(utils::generate_presale_key(item_id));
+        assert!(presale.map(|state| state.pledges == 0).unwrap_or(true));
+        database::delete(utils::generate_presale_key(item_id));
+    }
+    assert!(item.start_date <= item.expired_date);
+    assert!(item.royalty as U128 <= MAX_PERCENTAGE);
+    if item.buyer_limit.max_per_window != 0 {
+        assert_ne!(item.buyer_limit.window, 0);
+    }
+    if item.dispute_window != 0 {
+        assert!(resource_ddo.accountant_contract_address.is_some());
+        // the fee of auctions and pre-sales is paid by the marketplace contract after the sale ends,
+        // when the buyer can not sign for the escrow
+        match &item.sale_mode {
+            SaleMode::Auction(_) | SaleMode::Presale(_) => panic!("escrow not supported"),
+            _ => {}
+        }
+    }
+    for i in 1..item.price_tiers.len() {
+        assert!(item.price_tiers[i - 1].min_n < item.price_tiers[i].min_n);
+    }
+    for (i, alt_fee) in item.alt_fees.iter().enumerate() {
+        assert!(!alt_fee.same_currency(&item.fee));
+        assert!(!item.alt_fees[..i].iter().any(|f| f.same_currency(alt_fee)));
+    }
 
-    //verify token_template_id creator sig
-    // authorize mp address
-    verify_auth(
-        &resource_ddo.dtoken_contract_address,
-        item.token_template_ids.as_slice(),
-    );
+    if let Some(old) = &resource {
+        // the split policy registered when the item was published is kept
+        assert!(
+            old.resource_ddo.split_policy_contract_address
+                == resource_ddo.split_policy_contract_address
+        );
+        item.sold = old.item.sold;
+    }
 
     let seller = SellerItemInfo::new(item.clone(), resource_ddo.clone());
+    if let Some(old) = &resource {
+        index_item(
+            item_id,
+            &old.resource_ddo.manager,
+            &old.item.token_template_ids,
+            false,
+        );
+    }
+    // the items published before the item indexes were introduced are indexed when they are updated
+    index_add(KEY_ALL_ITEMS, item_id);
+    index_item(
+        item_id,
+        &resource_ddo.manager,
+        &item.token_template_ids,
+        true,
+    );
     database::put(utils::generate_seller_item_info_key(item_id), seller);
 
     //invoke split_policy contract
-    let split_addr = get_split_policy_contract();
-    assert!(split_policy::register(
-        &resource_ddo
+    if is_publish {
+        let split_addr = resource_ddo
             .split_policy_contract_address
-            .unwrap_or(split_addr),
-        item_id,
-        split_policy_param_bytes
-    ));
+            .unwrap_or(get_split_policy_contract());
+        match split_signer {
+            Some(receiver) => assert!(split_policy::register_signed(
+                &split_addr,
+                item_id,
+                split_policy_param_bytes,
+                receiver
+            )),
+            None => assert!(split_policy::register(
+                &split_addr,
+                item_id,
+                split_policy_param_bytes
+            )),
+        }
+    }
 
     //event
     let mut method = "dtokenSellerPublish";
@@ -206,6 +538,10 @@ pub fn dtoken_seller_publish_inner(
     true
 }
 
+/// update a published item, need the signatures of the item manager and the marketplace admin
+///
+/// the sold count of the item and the split policy registered when the item was published are kept,
+/// `split_policy_param_bytes` is not used and `split_policy_contract_address` of the ddo can not be changed
 pub fn update(
     resource_id: &[u8],
     resource_ddo: ResourceDDO,
@@ -227,7 +563,19 @@ pub fn delete(resource_id: &[u8]) -> bool {
             .unwrap();
     let admin = get_admin();
     assert!(check_witness(&item_info.resource_ddo.manager) || check_witness(&admin));
+    assert!(database::get::<_, Bid>(utils::generate_auction_bid_key(resource_id)).is_none());
+    let presale = database::get::<_, PresaleState>(utils::generate_presale_key(resource_id));
+    assert!(presale.map(|state| state.pledges == 0).unwrap_or(true));
+    database::delete(utils::generate_presale_key(resource_id));
     database::delete(utils::generate_seller_item_info_key(resource_id));
+    index_remove(KEY_ALL_ITEMS, resource_id);
+    index_item(
+        resource_id,
+        &item_info.resource_ddo.manager,
+        &item_info.item.token_template_ids,
+        false,
+    );
+    database::delete(utils::generate_item_paused_key(resource_id));
     EventBuilder::new()
         .string("delete")
         .bytearray(resource_id)
@@ -235,157 +583,1654 @@ pub fn delete(resource_id: &[u8]) -> bool {
     true
 }
 
-pub fn get_seller_item_info(resource_id: &[u8]) -> Vec<u8> {
-    let r = runtime::storage_read(utils::generate_seller_item_info_key(resource_id).as_slice())
-        .map(|val: Vec<u8>| val);
-    if let Some(rr) = r {
-        rr
-    } else {
-        vec![]
-    }
-}
-
-/// buy dtoken from reseller
+/// update the price of an item, the sold count and split policy registration are kept
 ///
-/// The seller can sell what he bought before he used it
+/// need the item manager signature
 ///
-/// `resource_id` used to mark the only commodity in the chain
-///
-/// `n` is the number of purchases
-///
-/// `buyer_account` is buyer address, need this address signature
+/// `count` is the new unit price, the currency can not be changed
 ///
-/// `reseller_account` is reseller address, need this address signature
-pub fn buy_dtoken_from_reseller(
-    resource_id: &[u8],
-    n: U128,
-    buyer_account: &Address,
-    reseller_account: &Address,
-) -> bool {
-    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(reseller_account));
-    let item_info =
-        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
-            .unwrap();
-    let oi = OrderId {
-        item_id: resource_id.to_vec(),
-        tx_hash: current_txhash(),
-    };
-    let split_contract = get_split_policy_contract();
-    assert!(transfer_fee(
-        &oi,
-        buyer_account,
-        item_info.resource_ddo.accountant_contract_address,
-        &item_info
-            .resource_ddo
-            .split_policy_contract_address
-            .unwrap_or(split_contract),
-        item_info.item.fee.clone(),
-        n
-    ));
-
-    transfer_dtoken(
-        &item_info.resource_ddo.dtoken_contract_address,
-        item_info.item.token_template_ids.as_slice(),
-        reseller_account,
-        buyer_account,
-        n,
+/// `price_tiers` is the new volume price schedule, can be empty
+pub fn update_price(resource_id: &[u8], count: U128, price_tiers: Vec<PriceTier>) -> bool {
+    let mut item_info = get_managed_item(resource_id);
+    for i in 1..price_tiers.len() {
+        assert!(price_tiers[i - 1].min_n < price_tiers[i].min_n);
+    }
+    item_info.item.fee.count = u64::try_from(count).expect("price overflow");
+    item_info.item.price_tiers = price_tiers;
+    database::put(
+        utils::generate_seller_item_info_key(resource_id),
+        &item_info,
     );
     EventBuilder::new()
-        .string("buyDTokenFromReseller")
+        .string("updatePrice")
         .bytearray(resource_id)
-        .number(n)
-        .address(buyer_account)
-        .address(reseller_account)
+        .number(count)
         .notify();
     true
 }
 
-/// Buy more than one dtoken at a time
-///
-/// `resource_ids` is array of resource_id which used to mark the only commodity in the chain
-///
-/// `ns` is array of n which is the number of purchases. the length of resource_ids must be the same with the length of ns.
+/// add stocks to an item, need the item manager signature
 ///
-/// `buyer_account` is buyer address, need this address signature
-pub fn buy_dtokens(
-    resource_ids: Vec<Vec<u8>>,
-    ns: Vec<U128>,
-    buyer_account: &Address,
-    payer: &Address,
-) -> Vec<Vec<Vec<u8>>> {
-    let l = resource_ids.len();
-    assert_eq!(l, ns.len());
-    (0..l)
-        .map(|i| buy_dtoken(resource_ids[i].as_slice(), ns[i], buyer_account, payer))
-        .collect::<Vec<Vec<Vec<u8>>>>()
-}
-
-fn get_token_template_ids(resource_id: &[u8]) -> Vec<Vec<u8>> {
-    let item_info =
-        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
-            .unwrap();
-    return item_info.item.token_template_ids;
+/// `n` is the number of stocks to add
+pub fn add_stocks(resource_id: &[u8], n: U128) -> bool {
+    let mut item_info = get_managed_item(resource_id);
+    let stocks = n.checked_add(item_info.item.stocks as U128).unwrap();
+    item_info.item.stocks = u64::try_from(stocks).expect("stocks overflow");
+    database::put(
+        utils::generate_seller_item_info_key(resource_id),
+        &item_info,
+    );
+    EventBuilder::new()
+        .string("addStocks")
+        .bytearray(resource_id)
+        .number(n)
+        .number(item_info.item.stocks as U128)
+        .notify();
+    true
 }
 
-/// buy dtoken
-///
-/// `resource_id` used to mark the only commodity in the chain
-///
-/// `n` is the number of purchases
+/// extend the expired date of an item, need the item manager signature
 ///
-/// `buyer_account` is buyer address, need this address signature
-pub fn buy_dtoken(
-    resource_id: &[u8],
-    n: U128,
-    buyer_account: &Address,
-    payer: &Address,
-) -> Vec<Vec<u8>> {
-    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
-    let mut item_info =
-        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
-            .unwrap();
-    let now = runtime::timestamp();
-    assert!(now <= item_info.item.expired_date);
-    assert!(item_info.item.sold <= item_info.item.stocks);
-    item_info.item.sold = n.checked_add(item_info.item.sold as U128).unwrap() as u64;
-    assert!(item_info.item.sold <= item_info.item.stocks);
-    let oi = OrderId {
-        item_id: resource_id.to_vec(),
-        tx_hash: current_txhash(),
-    };
-    assert!(transfer_fee(
-        &oi,
-        payer,
-        item_info.resource_ddo.accountant_contract_address.clone(),
-        &item_info
-            .resource_ddo
-            .split_policy_contract_address
-            .unwrap_or(get_split_policy_contract()),
-        item_info.item.fee.clone(),
-        n
-    ));
+/// `expired_date` is the new expired date, can not be earlier than the current one
+pub fn extend_expiry(resource_id: &[u8], expired_date: u64) -> bool {
+    let mut item_info = get_managed_item(resource_id);
+    assert!(expired_date >= item_info.item.expired_date);
+    item_info.item.expired_date = expired_date;
     database::put(
         utils::generate_seller_item_info_key(resource_id),
         &item_info,
     );
-    //TODO
-    let token_ids = generate_dtoken(
-        &item_info.resource_ddo.dtoken_contract_address,
-        item_info.item.token_template_ids.as_slice(),
-        buyer_account,
-        n,
-    );
     EventBuilder::new()
-        .string("buyDToken")
+        .string("extendExpiry")
         .bytearray(resource_id)
-        .number(n)
-        .address(buyer_account)
-        .address(payer)
+        .number(expired_date as U128)
         .notify();
-    token_ids
+    true
 }
 
-/// buy_dtoken_reward
+/// swap the token templates of an item, need the item manager and marketplace admin signature
+///
+/// `token_template_ids` are the new token templates, the marketplace contract will be authorized to generate them
+pub fn update_token_templates(resource_id: &[u8], token_template_ids: Vec<Vec<u8>>) -> bool {
+    let mut item_info = get_managed_item(resource_id);
+    assert!(check_witness(&get_admin()));
+    assert_ne!(token_template_ids.len(), 0);
+    verify_auth(
+        &item_info.resource_ddo.dtoken_contract_address,
+        token_template_ids.as_slice(),
+    );
+    let manager = &item_info.resource_ddo.manager;
+    index_item(
+        resource_id,
+        manager,
+        &item_info.item.token_template_ids,
+        false,
+    );
+    index_item(resource_id, manager, &token_template_ids, true);
+    index_add(KEY_ALL_ITEMS, resource_id);
+    item_info.item.token_template_ids = token_template_ids;
+    database::put(
+        utils::generate_seller_item_info_key(resource_id),
+        &item_info,
+    );
+    let mut sink = Sink::new(64);
+    sink.write(&item_info.item.token_template_ids);
+    EventBuilder::new()
+        .string("updateTokenTemplates")
+        .bytearray(resource_id)
+        .bytearray(sink.bytes())
+        .notify();
+    true
+}
+
+/// the number of published items
+pub fn get_item_count() -> u64 {
+    index_count(KEY_ALL_ITEMS)
+}
+
+/// enumerate the resource ids of the published items
+///
+/// `start` is the index of the first item, `limit` is the max number of items returned, at most 100.
+/// the order changes when an item is deleted
+pub fn get_items(start: u64, limit: u64) -> Vec<Vec<u8>> {
+    index_page(KEY_ALL_ITEMS, start, limit)
+}
+
+/// the number of items managed by `manager`
+pub fn get_manager_item_count(manager: &Address) -> u64 {
+    index_count(utils::generate_manager_items_key(manager).as_slice())
+}
+
+/// enumerate the resource ids of the items managed by `manager`, the same paging as `get_items`
+pub fn get_items_by_manager(manager: &Address, start: u64, limit: u64) -> Vec<Vec<u8>> {
+    index_page(
+        utils::generate_manager_items_key(manager).as_slice(),
+        start,
+        limit,
+    )
+}
+
+/// the number of items which sell dtokens of `token_template_id`
+pub fn get_template_item_count(token_template_id: &[u8]) -> u64 {
+    index_count(utils::generate_template_items_key(token_template_id).as_slice())
+}
+
+/// enumerate the resource ids of the items which sell dtokens of `token_template_id`, the same paging as `get_items`
+pub fn get_items_by_token_template(
+    token_template_id: &[u8],
+    start: u64,
+    limit: u64,
+) -> Vec<Vec<u8>> {
+    index_page(
+        utils::generate_template_items_key(token_template_id).as_slice(),
+        start,
+        limit,
+    )
+}
+
+/// add the items published before the item indexes were introduced to the indexes, need the contract admin signature
+///
+/// the items already indexed are skipped, so the method can be invoked again with overlapping `resource_ids`
+pub fn index_items(resource_ids: Vec<Vec<u8>>) -> bool {
+    assert!(check_witness(CONTRACT_COMMON.admin()));
+    for resource_id in resource_ids.iter() {
+        let item_info = database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(
+            resource_id.as_slice(),
+        ))
+        .expect("item not published");
+        index_add(KEY_ALL_ITEMS, resource_id.as_slice());
+        index_item(
+            resource_id.as_slice(),
+            &item_info.resource_ddo.manager,
+            &item_info.item.token_template_ids,
+            true,
+        );
+    }
+    EventBuilder::new()
+        .string("indexItems")
+        .number(resource_ids.len() as U128)
+        .notify();
+    true
+}
+
+// inner method
+//
+// add the item to or remove it from the indexes of its manager and token templates
+fn index_item(resource_id: &[u8], manager: &Address, token_template_ids: &[Vec<u8>], add: bool) {
+    let mut lists = vec![utils::generate_manager_items_key(manager)];
+    for token_template_id in token_template_ids.iter() {
+        lists.push(utils::generate_template_items_key(token_template_id));
+    }
+    for list in lists.iter() {
+        if add {
+            index_add(list.as_slice(), resource_id);
+        } else {
+            index_remove(list.as_slice(), resource_id);
+        }
+    }
+}
+
+fn index_count(list: &[u8]) -> u64 {
+    database::get::<_, u64>(list).unwrap_or(0)
+}
+
+// do nothing if the item is already in the list
+fn index_add(list: &[u8], resource_id: &[u8]) {
+    let position_key = utils::generate_item_position_key(list, resource_id);
+    if runtime::storage_read(position_key.as_slice()).is_some() {
+        return;
+    }
+    let count = index_count(list);
+    database::put(utils::generate_item_index_key(list, count), resource_id);
+    database::put(position_key, count);
+    database::put(list, count + 1);
+}
+
+// move the last item to the position of the removed one, do nothing if the item is not in the list
+fn index_remove(list: &[u8], resource_id: &[u8]) {
+    let position_key = utils::generate_item_position_key(list, resource_id);
+    let position = match database::get::<_, u64>(position_key.as_slice()) {
+        Some(position) => position,
+        None => return,
+    };
+    let last = index_count(list) - 1;
+    if position != last {
+        let last_id =
+            database::get::<_, Vec<u8>>(utils::generate_item_index_key(list, last)).unwrap();
+        database::put(utils::generate_item_index_key(list, position), &last_id);
+        database::put(
+            utils::generate_item_position_key(list, last_id.as_slice()),
+            position,
+        );
+    }
+    database::delete(utils::generate_item_index_key(list, last));
+    database::delete(position_key);
+    database::put(list, last);
+}
+
+fn index_page(list: &[u8], start: u64, limit: u64) -> Vec<Vec<u8>> {
+    let end = index_count(list).min(start.saturating_add(limit.min(MAX_PAGE_SIZE)));
+    let mut res = vec![];
+    for i in start..end {
+        res.push(database::get::<_, Vec<u8>>(utils::generate_item_index_key(list, i)).unwrap());
+    }
+    res
+}
+
+// load an item and check the item manager signature
+fn get_managed_item(resource_id: &[u8]) -> SellerItemInfo {
+    let item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    assert!(check_witness(&item_info.resource_ddo.manager));
+    item_info
+}
+
+/// pause the sale of an item, the sold count and other item information are kept
+///
+/// need the item manager signature
+pub fn pause_item(resource_id: &[u8]) -> bool {
+    set_item_paused(resource_id, true)
+}
+
+/// resume the sale of a paused item
+///
+/// need the item manager signature
+pub fn resume_item(resource_id: &[u8]) -> bool {
+    set_item_paused(resource_id, false)
+}
+
+fn set_item_paused(resource_id: &[u8], paused: bool) -> bool {
+    get_managed_item(resource_id);
+    assert_ne!(is_item_paused(resource_id), paused);
+    let key = utils::generate_item_paused_key(resource_id);
+    let method = if paused {
+        database::put(key, true);
+        "pauseItem"
+    } else {
+        database::delete(key);
+        "resumeItem"
+    };
+    EventBuilder::new()
+        .string(method)
+        .bytearray(resource_id)
+        .notify();
+    true
+}
+
+/// query whether the sale of an item is paused
+pub fn is_item_paused(resource_id: &[u8]) -> bool {
+    database::get::<_, bool>(utils::generate_item_paused_key(resource_id)).unwrap_or(false)
+}
+
+// the item can be bought only if it is not paused and `now` is within [start_date, expired_date]
+fn assert_on_sale(resource_id: &[u8], item: &DTokenItem, now: u64) {
+    assert!(!is_item_paused(resource_id));
+    assert!(now >= item.start_date);
+    assert!(now <= item.expired_date);
+}
+
+/// add buyers to the access list of an item, need the item manager signature
+///
+/// the access list is an allowlist or a denylist according to `buyer_access` of the item
+pub fn add_access_list(resource_id: &[u8], buyers: Vec<Address>) -> bool {
+    get_managed_item(resource_id);
+    for buyer in buyers.iter() {
+        database::put(utils::generate_access_list_key(resource_id, buyer), true);
+    }
+    EventBuilder::new()
+        .string("addAccessList")
+        .bytearray(resource_id)
+        .address_list(buyers.as_slice())
+        .notify();
+    true
+}
+
+/// remove buyers from the access list of an item, need the item manager signature
+pub fn remove_access_list(resource_id: &[u8], buyers: Vec<Address>) -> bool {
+    get_managed_item(resource_id);
+    for buyer in buyers.iter() {
+        database::delete(utils::generate_access_list_key(resource_id, buyer));
+    }
+    EventBuilder::new()
+        .string("removeAccessList")
+        .bytearray(resource_id)
+        .address_list(buyers.as_slice())
+        .notify();
+    true
+}
+
+/// query whether the buyer is in the access list of an item
+pub fn is_in_access_list(resource_id: &[u8], buyer: &Address) -> bool {
+    database::get::<_, bool>(utils::generate_access_list_key(resource_id, buyer)).unwrap_or(false)
+}
+
+// `buyer_ont_id` is the ONT ID controlled by the buyer, it is required if the item requires a credential
+fn assert_buyer_allowed(
+    resource_id: &[u8],
+    item: &DTokenItem,
+    buyer: &Address,
+    buyer_ont_id: Option<&[u8]>,
+) {
+    match item.buyer_access {
+        BuyerAccess::Open => {}
+        BuyerAccess::Allowlist => assert!(is_in_access_list(resource_id, buyer)),
+        BuyerAccess::Denylist => assert!(!is_in_access_list(resource_id, buyer)),
+    }
+    if let Some(credential) = &item.credential {
+        let buyer_ont_id = buyer_ont_id.expect("credential required");
+        let claim = ontid::get_attribute_by_key(credential.issuer.as_slice(), buyer_ont_id)
+            .expect("credential not issued");
+        assert_eq!(claim.as_slice(), credential.claim.as_slice());
+    }
+}
+
+/// query how many units of an item a buyer has bought
+pub fn get_buyer_purchase(resource_id: &[u8], buyer: &Address) -> BuyerPurchase {
+    database::get::<_, BuyerPurchase>(utils::generate_buyer_purchase_key(resource_id, buyer))
+        .unwrap_or(BuyerPurchase::default())
+}
+
+// record the purchase of a buyer, panic if the purchase limit of the item is exceeded
+fn record_buyer_purchase(
+    resource_id: &[u8],
+    item: &DTokenItem,
+    buyer: &Address,
+    n: U128,
+    now: u64,
+) {
+    let limit = &item.buyer_limit;
+    if limit.max_per_buyer == 0 && limit.max_per_window == 0 {
+        return;
+    }
+    let n = u64::try_from(n).unwrap();
+    let mut purchase = get_buyer_purchase(resource_id, buyer);
+    purchase.total = purchase.total.checked_add(n).unwrap();
+    if limit.max_per_buyer != 0 {
+        assert!(purchase.total <= limit.max_per_buyer);
+    }
+    if limit.max_per_window != 0 {
+        if now >= purchase.window_start.checked_add(limit.window).unwrap() {
+            purchase.window_start = now;
+            purchase.window_count = 0;
+        }
+        purchase.window_count = purchase.window_count.checked_add(n).unwrap();
+        assert!(purchase.window_count <= limit.max_per_window);
+    }
+    database::put(
+        utils::generate_buyer_purchase_key(resource_id, buyer),
+        purchase,
+    );
+}
+
+pub fn get_seller_item_info(resource_id: &[u8]) -> Vec<u8> {
+    let r = runtime::storage_read(utils::generate_seller_item_info_key(resource_id).as_slice())
+        .map(|val: Vec<u8>| val);
+    if let Some(rr) = r {
+        rr
+    } else {
+        vec![]
+    }
+}
+
+/// buy dtoken from reseller
+///
+/// The seller can sell what he bought before he used it, the royalty of the item goes to
+/// the split policy of the item and the rest of the fee goes to the reseller
+///
+/// no ONT ID is passed, so the dtokens of an item which requires a credential can not be resold
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `n` is the number of purchases
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `reseller_account` is reseller address, need this address signature
+pub fn buy_dtoken_from_reseller(
+    resource_id: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    reseller_account: &Address,
+) -> bool {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(reseller_account));
+    let item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    assert_buyer_allowed(resource_id, &item_info.item, buyer_account, None);
+    let oi = new_order_id(resource_id);
+    let now = runtime::timestamp();
+    let (fee, tiers) = item_info.item.pricing(now);
+    let amt = fee.total_amount(tiers.as_slice(), n);
+    assert!(pay_resale(
+        &oi,
+        buyer_account,
+        reseller_account,
+        Some(&item_info),
+        &fee,
+        amt
+    ));
+
+    transfer_dtoken(
+        &item_info.resource_ddo.dtoken_contract_address,
+        item_info.item.token_template_ids.as_slice(),
+        reseller_account,
+        buyer_account,
+        n,
+    );
+    EventBuilder::new()
+        .string("buyDTokenFromReseller")
+        .bytearray(resource_id)
+        .number(n)
+        .address(buyer_account)
+        .address(reseller_account)
+        .notify();
+    true
+}
+
+/// reseller posts a standing sell order of the dtoken he bought before
+///
+/// the reseller should approve the marketplace contract to transfer `amount` dtokens first,
+/// the dtokens are escrowed in the marketplace contract until the order is filled or canceled
+///
+/// `seller` is reseller address, need this address signature
+///
+/// `resource_id` is the item the dtoken was bought from
+///
+/// `token_id` is the dtoken to sell, must be generated from a token template of the item
+///
+/// `amount` is the number of dtokens to sell
+///
+/// `price` is the currency and unit price, `price.count` is the unit price
+///
+/// `expired_date` is the timestamp after which the order can not be filled, must be in the future
+///
+/// return the order id
+pub fn post_sell_order(
+    seller: &Address,
+    resource_id: &[u8],
+    token_id: &[u8],
+    amount: U128,
+    price: Fee,
+    expired_date: u64,
+) -> U128 {
+    assert!(check_witness(seller));
+    assert_ne!(amount, 0);
+    assert!(expired_date > runtime::timestamp());
+    let item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    let dtoken_contract = get_item_dtoken_contract(&item_info, token_id);
+    let self_addr = address();
+    assert!(transfer_from_oep8(
+        &dtoken_contract,
+        &self_addr,
+        seller,
+        &self_addr,
+        token_id,
+        amount
+    ));
+    let order_id = database::get::<_, U128>(KEY_SELL_ORDER_ID).unwrap_or(0);
+    database::put(KEY_SELL_ORDER_ID, order_id + 1);
+    let order = SellOrder {
+        resource_id: resource_id.to_vec(),
+        seller: seller.clone(),
+        dtoken_contract,
+        token_id: token_id.to_vec(),
+        amount,
+        price,
+        expired_date,
+    };
+    database::put(utils::generate_sell_order_key(order_id), &order);
+    EventBuilder::new()
+        .string("postSellOrder")
+        .number(order_id)
+        .address(seller)
+        .bytearray(resource_id)
+        .bytearray(token_id)
+        .number(amount)
+        .number(order.price.count as U128)
+        .notify();
+    order_id
+}
+
+/// buyer fills a sell order, the payment goes to the reseller directly
+///
+/// sell orders of an item which requires a credential can not be filled, as the buyer's ONT ID is not passed
+///
+/// `order_id` is the id returned by `post_sell_order`
+///
+/// `n` is the number of dtokens to buy, can be less than the remaining amount of the order
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+pub fn fill_sell_order(order_id: U128, n: U128, buyer_account: &Address, payer: &Address) -> bool {
+    assert!(check_witness(buyer_account) && check_witness(payer));
+    assert_ne!(n, 0);
+    let key = utils::generate_sell_order_key(order_id);
+    let mut order = database::get::<_, SellOrder>(key.as_slice()).expect("no sell order");
+    assert!(runtime::timestamp() <= order.expired_date);
+    order.amount = order.amount.checked_sub(n).expect("not enough dtoken");
+    let amt = n.checked_mul(order.price.count as U128).unwrap();
+    let item_info = database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(
+        order.resource_id.as_slice(),
+    ));
+    if let Some(item_info) = &item_info {
+        assert_buyer_allowed(
+            order.resource_id.as_slice(),
+            &item_info.item,
+            buyer_account,
+            None,
+        );
+    }
+    let oi = new_order_id(order.resource_id.as_slice());
+    assert!(pay_resale(
+        &oi,
+        payer,
+        &order.seller,
+        item_info.as_ref(),
+        &order.price,
+        amt
+    ));
+    assert!(transfer_oep8(
+        &order.dtoken_contract,
+        &address(),
+        buyer_account,
+        order.token_id.as_slice(),
+        n
+    ));
+    if order.amount == 0 {
+        database::delete(key);
+    } else {
+        database::put(key, &order);
+    }
+    EventBuilder::new()
+        .string("fillSellOrder")
+        .number(order_id)
+        .number(n)
+        .address(buyer_account)
+        .address(payer)
+        .notify();
+    true
+}
+
+/// reseller cancels a sell order, the remaining dtokens are returned to him
+///
+/// `order_id` is the id returned by `post_sell_order`, need the reseller signature
+pub fn cancel_sell_order(order_id: U128) -> bool {
+    let key = utils::generate_sell_order_key(order_id);
+    let order = database::get::<_, SellOrder>(key.as_slice()).expect("no sell order");
+    assert!(check_witness(&order.seller));
+    database::delete(key);
+    assert!(transfer_oep8(
+        &order.dtoken_contract,
+        &address(),
+        &order.seller,
+        order.token_id.as_slice(),
+        order.amount
+    ));
+    EventBuilder::new()
+        .string("cancelSellOrder")
+        .number(order_id)
+        .number(order.amount)
+        .notify();
+    true
+}
+
+/// query sell order by order id, return empty bytes if the order does not exist
+pub fn get_sell_order(order_id: U128) -> Vec<u8> {
+    runtime::storage_read(utils::generate_sell_order_key(order_id).as_slice()).unwrap_or_default()
+}
+
+/// query order by order id, return empty bytes if the order does not exist
+pub fn get_order(order_id: &[u8]) -> Vec<u8> {
+    runtime::storage_read(utils::generate_order_key(order_id).as_slice()).unwrap_or_default()
+}
+
+/// update the status of an escrowed order after the fee is released to the seller or refunded to the payer
+///
+/// invoked by the accountant contract holding the fee of the order
+///
+/// `order_id` is the id of the order
+///
+/// `refunded` is true if the fee is refunded to the payer, the dtokens of the order are kept by the buyer
+/// and the sold count of the item is not changed
+pub fn update_order_status(order_id: &[u8], refunded: bool) -> bool {
+    let key = utils::generate_order_key(order_id);
+    let mut order = database::get::<_, Order>(key.as_slice()).expect("no order");
+    assert!(matches!(order.status, OrderStatus::Escrowed));
+    assert!(check_witness(
+        order.accountant.as_ref().expect("no accountant")
+    ));
+    order.status = if refunded {
+        OrderStatus::Refunded
+    } else {
+        OrderStatus::Released
+    };
+    database::put(key, &order);
+    EventBuilder::new()
+        .string("updateOrderStatus")
+        .bytearray(order_id)
+        .bool(refunded)
+        .notify();
+    true
+}
+
+/// the number of orders of the buyer
+pub fn get_buyer_order_count(buyer: &Address) -> u64 {
+    database::get::<_, u64>(utils::generate_buyer_order_count_key(buyer)).unwrap_or(0)
+}
+
+/// query the orders of the buyer in the order of purchase
+///
+/// `start` is the index of the first order, `limit` is the max number of orders returned, at most 100
+pub fn get_buyer_orders(buyer: &Address, start: u64, limit: u64) -> Vec<Order> {
+    get_order_page(utils::generate_buyer_order_count_key(buyer), start, limit)
+}
+
+/// the number of orders of the item
+pub fn get_item_order_count(resource_id: &[u8]) -> u64 {
+    database::get::<_, u64>(utils::generate_item_order_count_key(resource_id)).unwrap_or(0)
+}
+
+/// query the orders of the item in the order of purchase
+///
+/// `start` is the index of the first order, `limit` is the max number of orders returned, at most 100
+pub fn get_item_orders(resource_id: &[u8], start: u64, limit: u64) -> Vec<Order> {
+    get_order_page(
+        utils::generate_item_order_count_key(resource_id),
+        start,
+        limit,
+    )
+}
+
+// the dtoken contract which generated `token_id` for the item, panic if the token does not belong to the item
+fn get_item_dtoken_contract(item_info: &SellerItemInfo, token_id: &[u8]) -> Address {
+    let dtokens = &item_info.resource_ddo.dtoken_contract_address;
+    let default_dtoken = get_dtoken_contract();
+    let index = item_info
+        .item
+        .token_template_ids
+        .iter()
+        .enumerate()
+        .position(|(i, template_id)| {
+            let dtoken = dtokens.get(i).unwrap_or(&default_dtoken);
+            &get_template_id_by_token_id(dtoken, token_id) == template_id
+        })
+        .expect("token does not belong to the item");
+    dtokens.get(index).unwrap_or(&default_dtoken).clone()
+}
+
+/// Buy more than one dtoken at a time
+///
+/// `resource_ids` is array of resource_id which used to mark the only commodity in the chain
+///
+/// `ns` is array of n which is the number of purchases. the length of resource_ids must be the same with the length of ns.
+///
+/// `buyer_account` is buyer address, need this address signature
+pub fn buy_dtokens(
+    resource_ids: Vec<Vec<u8>>,
+    ns: Vec<U128>,
+    buyer_account: &Address,
+    payer: &Address,
+) -> Vec<Vec<Vec<u8>>> {
+    let l = resource_ids.len();
+    assert_eq!(l, ns.len());
+    (0..l)
+        .map(|i| buy_dtoken(resource_ids[i].as_slice(), ns[i], buyer_account, payer))
+        .collect::<Vec<Vec<Vec<u8>>>>()
+}
+
+fn get_token_template_ids(resource_id: &[u8]) -> Vec<Vec<u8>> {
+    let item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    return item_info.item.token_template_ids;
+}
+
+/// buy dtoken
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `n` is the number of purchases
+///
+/// `buyer_account` is buyer address, need this address signature
+pub fn buy_dtoken(
+    resource_id: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    payer: &Address,
+) -> Vec<Vec<u8>> {
+    buy_dtoken_with_max_price(resource_id, n, buyer_account, payer, U128::max_value())
+}
+
+/// buy dtoken, fail if the current unit price is higher than `max_unit_price`
+///
+/// the unit price of a Dutch item decays over time, `max_unit_price` protects the buyer
+/// from being charged more than the price he signed for
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `n` is the number of purchases
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+///
+/// `max_unit_price` is the highest unit price the buyer accepts
+pub fn buy_dtoken_with_max_price(
+    resource_id: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    payer: &Address,
+    max_unit_price: U128,
+) -> Vec<Vec<u8>> {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
+    buy_dtoken_inner(
+        resource_id,
+        n,
+        buyer_account,
+        payer,
+        BuyOption {
+            max_unit_price,
+            ..BuyOption::default()
+        },
+    )
+}
+
+/// buy dtoken and pay with one of the currencies accepted by the item
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `n` is the number of purchases
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+///
+/// `fee_index` is 0 to pay with `fee` of the item, or i to pay with `alt_fees[i - 1]`
+///
+/// `max_unit_price` is the highest unit price the buyer accepts, in the chosen currency
+pub fn buy_dtoken_with_currency(
+    resource_id: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    payer: &Address,
+    fee_index: U128,
+    max_unit_price: U128,
+) -> Vec<Vec<u8>> {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
+    buy_dtoken_inner(
+        resource_id,
+        n,
+        buyer_account,
+        payer,
+        BuyOption {
+            max_unit_price,
+            fee_index: usize::try_from(fee_index).expect("invalid fee index"),
+            ..BuyOption::default()
+        },
+    )
+}
+
+/// buy dtoken of an item priced in a reference unit, the price is converted to the chosen currency
+/// by the oracle contract
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `n` is the number of purchases
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+///
+/// `fee_index` is 0 to pay with `fee` of the item, or i to pay with `alt_fees[i - 1]`
+///
+/// `max_price_age` is the maximum seconds since the oracle price was updated
+///
+/// `max_unit_price` is the highest unit price the buyer accepts, in the chosen currency
+pub fn buy_dtoken_with_oracle(
+    resource_id: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    payer: &Address,
+    fee_index: U128,
+    max_price_age: u64,
+    max_unit_price: U128,
+) -> Vec<Vec<u8>> {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
+    buy_dtoken_inner(
+        resource_id,
+        n,
+        buyer_account,
+        payer,
+        BuyOption {
+            max_unit_price,
+            fee_index: usize::try_from(fee_index).expect("invalid fee index"),
+            max_price_age: Some(max_price_age),
+            ..BuyOption::default()
+        },
+    )
+}
+
+/// buy dtoken of an item which requires a credential issued to the buyer's ONT ID
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `n` is the number of purchases
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+///
+/// `ont_id` is the ONT ID of the buyer, need the signature of the public key at `key_index`
+///
+/// `key_index` is the index of the public key of `ont_id` which signs the transaction
+pub fn buy_dtoken_with_credential(
+    resource_id: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    payer: &Address,
+    ont_id: &[u8],
+    key_index: U128,
+) -> Vec<Vec<u8>> {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
+    assert!(ontid::verify_signature(ont_id, key_index));
+    buy_dtoken_inner(
+        resource_id,
+        n,
+        buyer_account,
+        payer,
+        BuyOption {
+            buyer_ont_id: Some(ont_id),
+            ..BuyOption::default()
+        },
+    )
+}
+
+/// buy dtoken with a coupon signed by the item manager
+///
+/// the manager should have set his signing key by [`set_signing_key`](fn.set_signing_key.html),
+/// every purchase redeems the coupon once
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `n` is the number of purchases
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+///
+/// `coupon` is the coupon issued by the item manager
+///
+/// `sig` is the signature of `coupon.signed_message()`
+pub fn buy_dtoken_with_coupon(
+    resource_id: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    payer: &Address,
+    coupon: Coupon,
+    sig: &[u8],
+) -> Vec<Vec<u8>> {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
+    assert_eq!(coupon.marketplace, address());
+    assert_eq!(coupon.item_id.as_slice(), resource_id);
+    assert!(runtime::timestamp() <= coupon.expired_date);
+    if let Some(buyer) = &coupon.buyer {
+        assert_eq!(buyer, buyer_account);
+    }
+    let item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    verify_signature(
+        &item_info.resource_ddo.manager,
+        coupon.signed_message().as_slice(),
+        sig,
+    );
+    let key = utils::generate_coupon_redeemed_key(resource_id, coupon.coupon_id.as_slice());
+    let redeemed = database::get::<_, u64>(key.as_slice()).unwrap_or(0);
+    let redeemed = redeemed.checked_add(1).unwrap();
+    assert!(redeemed <= coupon.max_redemptions);
+    database::put(key, redeemed);
+    EventBuilder::new()
+        .string("redeemCoupon")
+        .bytearray(resource_id)
+        .bytearray(coupon.coupon_id.as_slice())
+        .address(buyer_account)
+        .number(redeemed as U128)
+        .notify();
+    buy_dtoken_inner(
+        resource_id,
+        n,
+        buyer_account,
+        payer,
+        BuyOption {
+            discount: Some(&coupon.discount),
+            ..BuyOption::default()
+        },
+    )
+}
+
+/// buy dtoken with a purchase intent signed by the buyer off-chain, the buyer does not need to sign the transaction
+///
+/// the buyer should have set his signing key by [`set_signing_key`](fn.set_signing_key.html),
+/// every nonce of the buyer can be used only once
+///
+/// `intent` is the purchase signed by the buyer, the dtokens are generated for `intent.buyer` by this marketplace
+///
+/// `sig` is the signature of `intent.signed_message()`
+///
+/// `payer` is the address who pay the fee, usually the relayer, need this address signature
+pub fn buy_dtoken_by_intent(intent: PurchaseIntent, sig: &[u8], payer: &Address) -> Vec<Vec<u8>> {
+    assert!(runtime::check_witness(payer));
+    assert_eq!(intent.marketplace, address());
+    assert!(runtime::timestamp() <= intent.deadline);
+    verify_signature(&intent.buyer, intent.signed_message().as_slice(), sig);
+    let key = utils::generate_intent_nonce_key(&intent.buyer, intent.nonce);
+    assert!(!database::get::<_, bool>(key.as_slice()).unwrap_or(false));
+    database::put(key, true);
+    EventBuilder::new()
+        .string("buyDTokenByIntent")
+        .bytearray(intent.item_id.as_slice())
+        .address(&intent.buyer)
+        .address(payer)
+        .number(intent.nonce as U128)
+        .notify();
+    buy_dtoken_inner(
+        intent.item_id.as_slice(),
+        intent.n,
+        &intent.buyer,
+        payer,
+        BuyOption {
+            max_unit_price: intent.max_unit_price,
+            relayed: true,
+            ..BuyOption::default()
+        },
+    )
+}
+
+/// buy dtoken from a listing signed by the seller and the marketplace admin off-chain,
+/// the item is published by the first purchase
+///
+/// the seller and the admin should have set their signing keys by [`set_signing_key`](fn.set_signing_key.html),
+/// the token templates should have authorized this marketplace, and this marketplace should be trusted by the
+/// split policy contract to register on behalf of the seller, who must be a receiver of `listing.split_policy_param`.
+/// a listing can be published only once, before its expired date and before the seller revokes its nonce
+///
+/// the signatures are verified by every purchase, and an item can only be bought from the listing it is
+/// published from, items published in other ways are rejected
+///
+/// `listing` is the complete item, `listing.resource_ddo.manager` is the seller
+///
+/// `seller_sig` and `mp_sig` are the signatures of `listing.signed_message()` by the seller and the admin
+///
+/// `n` is the number of purchases
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+///
+/// `max_unit_price` is the highest unit price the buyer accepts
+pub fn buy_dtoken_from_listing(
+    listing: Listing,
+    seller_sig: &[u8],
+    mp_sig: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    payer: &Address,
+    max_unit_price: U128,
+) -> Vec<Vec<u8>> {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
+    let item_id = listing.item_id.as_slice();
+    let seller = &listing.resource_ddo.manager;
+    assert_eq!(listing.marketplace, address());
+    let msg = listing.signed_message();
+    verify_signature(seller, msg.as_slice(), seller_sig);
+    verify_signature(&get_admin(), msg.as_slice(), mp_sig);
+    let listing_hash = runtime::sha256(listing.to_bytes());
+    let key = utils::generate_listing_published_key(item_id);
+    match database::get::<_, H256>(key.as_slice()) {
+        Some(published) => assert!(published == listing_hash),
+        None => {
+            assert!(runtime::timestamp() <= listing.expired_date);
+            assert!(!is_listing_revoked(seller, listing.nonce));
+            // the creator's signature is not available, the templates must have authorized this marketplace
+            assert_authorized(
+                &listing.resource_ddo.dtoken_contract_address,
+                listing.item.token_template_ids.as_slice(),
+            );
+            database::put(key, &listing_hash);
+            assert!(publish_item(
+                item_id,
+                listing.resource_ddo.clone(),
+                listing.item.clone(),
+                listing.split_policy_param.as_slice(),
+                true,
+                Some(seller)
+            ));
+        }
+    }
+    buy_dtoken_inner(
+        item_id,
+        n,
+        buyer_account,
+        payer,
+        BuyOption {
+            max_unit_price,
+            ..BuyOption::default()
+        },
+    )
+}
+
+/// revoke the listings signed with `nonce` which have not been published, need the seller signature
+pub fn revoke_listing(seller: &Address, nonce: u64) -> bool {
+    assert!(check_witness(seller));
+    database::put(utils::generate_listing_nonce_key(seller, nonce), true);
+    EventBuilder::new()
+        .string("revokeListing")
+        .address(seller)
+        .number(nonce as U128)
+        .notify();
+    true
+}
+
+/// query whether the listings signed by `seller` with `nonce` are revoked
+pub fn is_listing_revoked(seller: &Address, nonce: u64) -> bool {
+    database::get::<_, bool>(utils::generate_listing_nonce_key(seller, nonce)).unwrap_or(false)
+}
+
+/// query whether the nonce of a purchase intent has been used
+pub fn is_intent_nonce_used(buyer: &Address, nonce: u64) -> bool {
+    database::get::<_, bool>(utils::generate_intent_nonce_key(buyer, nonce)).unwrap_or(false)
+}
+
+/// query how many times a coupon has been redeemed
+pub fn get_coupon_redeemed(resource_id: &[u8], coupon_id: &[u8]) -> u64 {
+    database::get::<_, u64>(utils::generate_coupon_redeemed_key(resource_id, coupon_id))
+        .unwrap_or(0)
+}
+
+// options of a purchase made by `buy_dtoken_inner`
+struct BuyOption<'a> {
+    max_unit_price: U128,
+    discount: Option<&'a Discount>,
+    buyer_ont_id: Option<&'a [u8]>,
+    fee_index: usize,
+    max_price_age: Option<u64>, // only set by `buy_dtoken_with_oracle`, the only way to buy oracle priced items
+    relayed: bool, // the buyer does not sign the transaction, the dtokens are generated by the marketplace
+}
+
+impl<'a> BuyOption<'a> {
+    fn default() -> Self {
+        BuyOption {
+            max_unit_price: U128::max_value(),
+            discount: None,
+            buyer_ont_id: None,
+            fee_index: 0,
+            max_price_age: None,
+            relayed: false,
+        }
+    }
+}
+
+// the signatures of buyer and payer should be checked by the caller
+fn buy_dtoken_inner(
+    resource_id: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    payer: &Address,
+    option: BuyOption,
+) -> Vec<Vec<u8>> {
+    let mut item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    let (fee, tiers, unit_price) =
+        prepare_purchase(resource_id, &mut item_info, n, buyer_account, &option);
+    let oi = new_order_id(resource_id);
+    let order = new_order(
+        &oi,
+        buyer_account,
+        payer,
+        n,
+        &fee,
+        unit_price,
+        fee.total_amount(tiers.as_slice(), n),
+    );
+    assert!(pay_item(
+        &oi,
+        buyer_account,
+        payer,
+        &item_info,
+        fee,
+        tiers,
+        n
+    ));
+    deliver_purchase(&item_info, order, option.relayed)
+}
+
+// check the purchase and update the sold count of the item, return the fee, price tiers and unit price to charge
+fn prepare_purchase(
+    resource_id: &[u8],
+    item_info: &mut SellerItemInfo,
+    n: U128,
+    buyer_account: &Address,
+    option: &BuyOption,
+) -> (Fee, Vec<PriceTier>, u64) {
+    let now = runtime::timestamp();
+    match &item_info.item.sale_mode {
+        SaleMode::Fixed => {}
+        SaleMode::Dutch(param) => assert!(now >= param.start_time),
+        SaleMode::Oracle(_) => {}
+        _ => panic!("item can not be bought directly"),
+    }
+    assert_on_sale(resource_id, &item_info.item, now);
+    assert_buyer_allowed(
+        resource_id,
+        &item_info.item,
+        buyer_account,
+        option.buyer_ont_id,
+    );
+    record_buyer_purchase(resource_id, &item_info.item, buyer_account, n, now);
+    assert!(item_info.item.sold <= item_info.item.stocks);
+    item_info.item.sold = n.checked_add(item_info.item.sold as U128).unwrap() as u64;
+    assert!(item_info.item.sold <= item_info.item.stocks);
+    let (mut fee, mut tiers) = item_info.item.pricing_in(now, option.fee_index);
+    if let SaleMode::Oracle(param) = &item_info.item.sale_mode {
+        let max_price_age = option.max_price_age.expect("oracle priced item");
+        fee.count = oracle_unit_price(&fee, param.ref_price, now, max_price_age);
+        tiers = vec![];
+    }
+    if let Some(discount) = option.discount {
+        fee.count = discount.apply(fee.unit_price(tiers.as_slice(), n));
+        tiers = vec![];
+    }
+    let unit_price = fee.unit_price(tiers.as_slice(), n);
+    assert!(unit_price as U128 <= option.max_unit_price);
+    (fee, tiers, unit_price)
+}
+
+// save the item, generate dtokens for the buyer and record the order after the fee is paid
+//
+// the dtokens are generated on behalf of the marketplace if the purchase is `relayed`
+fn deliver_purchase(item_info: &SellerItemInfo, mut order: Order, relayed: bool) -> Vec<Vec<u8>> {
+    database::put(
+        utils::generate_seller_item_info_key(order.resource_id.as_slice()),
+        item_info,
+    );
+    let dtokens = &item_info.resource_ddo.dtoken_contract_address;
+    let token_template_ids = item_info.item.token_template_ids.as_slice();
+    let token_ids = if relayed {
+        generate_dtoken_for_other(dtokens, token_template_ids, &order.buyer, order.n)
+    } else {
+        generate_dtoken(dtokens, token_template_ids, &order.buyer, order.n)
+    };
+    EventBuilder::new()
+        .string("buyDToken")
+        .bytearray(order.resource_id.as_slice())
+        .number(order.n)
+        .address(&order.buyer)
+        .address(&order.payer)
+        .number(order.fee.count as U128)
+        .notify();
+    set_escrow_status(&mut order, item_info);
+    order.token_ids = token_ids.clone();
+    record_order(&order);
+    token_ids
+}
+
+/// buy several items atomically
+///
+/// all the items are checked before any payment, then the fees paid to the same split contract in the same
+/// currency are aggregated, so that every recipient is paid once. items with an accountant contract are paid one by one,
+/// and so are the items of a split contract which does not support `transferWithdrawMulti`.
+///
+/// `resource_ids` is array of resource_id which used to mark the only commodity in the chain, can not contain duplicates
+///
+/// `ns` is array of n which is the number of purchases. the length of resource_ids must be the same with the length of ns.
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+///
+/// return the receipt of every item in the same order as `resource_ids`
+pub fn checkout(
+    resource_ids: Vec<Vec<u8>>,
+    ns: Vec<U128>,
+    buyer_account: &Address,
+    payer: &Address,
+) -> Vec<CartReceipt> {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
+    let l = resource_ids.len();
+    assert_eq!(l, ns.len());
+    assert_ne!(l, 0);
+    for i in 1..l {
+        assert!(!resource_ids[..i].contains(&resource_ids[i]));
+    }
+    let option = BuyOption::default();
+    let mut item_infos = Vec::with_capacity(l);
+    let mut prices = Vec::with_capacity(l);
+    for i in 0..l {
+        let mut item_info = database::get::<_, SellerItemInfo>(
+            utils::generate_seller_item_info_key(resource_ids[i].as_slice()),
+        )
+        .unwrap();
+        let price = prepare_purchase(
+            resource_ids[i].as_slice(),
+            &mut item_info,
+            ns[i],
+            buyer_account,
+            &option,
+        );
+        item_infos.push(item_info);
+        prices.push(price);
+    }
+
+    // (split contract, currency, indexes of the items)
+    let mut groups: Vec<(Address, Fee, Vec<usize>)> = vec![];
+    let order_ids: Vec<Vec<u8>> = resource_ids
+        .iter()
+        .map(|resource_id| new_order_id(resource_id.as_slice()))
+        .collect();
+    for i in 0..l {
+        let item_info = &item_infos[i];
+        let (fee, tiers, _) = &prices[i];
+        if item_info.resource_ddo.accountant_contract_address.is_some() {
+            assert!(pay_item(
+                &order_ids[i],
+                buyer_account,
+                payer,
+                item_info,
+                fee.clone(),
+                tiers.clone(),
+                ns[i]
+            ));
+            continue;
+        }
+        let split_addr = item_info
+            .resource_ddo
+            .split_policy_contract_address
+            .unwrap_or(get_split_policy_contract());
+        match groups
+            .iter_mut()
+            .find(|(addr, f, _)| addr == &split_addr && f.same_currency(fee))
+        {
+            Some((_, _, items)) => items.push(i),
+            None => groups.push((split_addr, fee.clone(), vec![i])),
+        }
+    }
+    for (split_addr, fee, items) in groups.iter() {
+        let keys: Vec<Vec<u8>> = items.iter().map(|&i| resource_ids[i].clone()).collect();
+        let amts: Vec<U128> = items
+            .iter()
+            .map(|&i| {
+                let (fee, tiers, _) = &prices[i];
+                fee.total_amount(tiers.as_slice(), ns[i])
+            })
+            .collect();
+        let res = wasm::call_contract(
+            split_addr,
+            ("transferWithdrawMulti", (payer, keys, amts, fee)),
+        );
+        if res.is_some() {
+            verify_result(res);
+            continue;
+        }
+        // the split contract does not support paying several items at once
+        for &i in items.iter() {
+            let (fee, tiers, _) = &prices[i];
+            assert!(pay_item(
+                &order_ids[i],
+                buyer_account,
+                payer,
+                &item_infos[i],
+                fee.clone(),
+                tiers.clone(),
+                ns[i]
+            ));
+        }
+    }
+
+    let mut receipts = Vec::with_capacity(l);
+    for i in 0..l {
+        let (fee, tiers, unit_price) = &prices[i];
+        let amount = fee.total_amount(tiers.as_slice(), ns[i]);
+        let order = new_order(
+            &order_ids[i],
+            buyer_account,
+            payer,
+            ns[i],
+            fee,
+            *unit_price,
+            amount,
+        );
+        let token_ids = deliver_purchase(&item_infos[i], order, false);
+        receipts.push(CartReceipt {
+            resource_id: resource_ids[i].clone(),
+            n: ns[i],
+            amount,
+            token_ids,
+        });
+    }
+    EventBuilder::new()
+        .string("checkout")
+        .address(buyer_account)
+        .address(payer)
+        .number(l as U128)
+        .notify();
+    receipts
+}
+
+/// publish a bundle of published items
+///
+/// need the signatures of the marketplace admin and the managers of all the component items
+///
+/// `bundle_id` used to mark the only bundle in the chain
+///
+/// `resource_ids` are the component items, which must be fixed price items priced in the currency of `fee`
+///
+/// `fee` is the currency and the price of one bundle
+///
+/// `expired_date` is the timestamp after which the bundle can not be bought
+pub fn publish_bundle(
+    bundle_id: &[u8],
+    resource_ids: Vec<Vec<u8>>,
+    fee: Fee,
+    expired_date: u64,
+) -> bool {
+    assert!(check_witness(&get_admin()));
+    let key = utils::generate_bundle_key(bundle_id);
+    assert!(database::get::<_, Bundle>(key.as_slice()).is_none());
+    assert!(resource_ids.len() > 1);
+    for (i, resource_id) in resource_ids.iter().enumerate() {
+        assert!(!resource_ids[..i].contains(resource_id));
+        let item_info = database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(
+            resource_id.as_slice(),
+        ))
+        .unwrap();
+        assert!(check_witness(&item_info.resource_ddo.manager));
+        assert!(matches!(item_info.item.sale_mode, SaleMode::Fixed));
+        assert!(item_info.item.fee.same_currency(&fee));
+    }
+    let bundle = Bundle {
+        resource_ids,
+        fee,
+        expired_date,
+    };
+    database::put(key, &bundle);
+    EventBuilder::new()
+        .string("publishBundle")
+        .bytearray(bundle_id)
+        .number(bundle.resource_ids.len() as U128)
+        .number(bundle.fee.count as U128)
+        .notify();
+    true
+}
+
+/// delete a bundle, the component items are not affected
+///
+/// need the signature of the marketplace admin or the manager of a component item
+pub fn delete_bundle(bundle_id: &[u8]) -> bool {
+    let key = utils::generate_bundle_key(bundle_id);
+    let bundle = database::get::<_, Bundle>(key.as_slice()).expect("no bundle");
+    let authorized = check_witness(&get_admin())
+        || bundle.resource_ids.iter().any(|resource_id| {
+            database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(
+                resource_id.as_slice(),
+            ))
+            .map(|item_info| check_witness(&item_info.resource_ddo.manager))
+            .unwrap_or(false)
+        });
+    assert!(authorized);
+    database::delete(key);
+    EventBuilder::new()
+        .string("deleteBundle")
+        .bytearray(bundle_id)
+        .notify();
+    true
+}
+
+/// query bundle by bundle id, return empty bytes if the bundle does not exist
+pub fn get_bundle(bundle_id: &[u8]) -> Vec<u8> {
+    runtime::storage_read(utils::generate_bundle_key(bundle_id).as_slice()).unwrap_or_default()
+}
+
+/// buy bundles
+///
+/// every component item is sold as if it was bought alone, the bundle price is split across
+/// the component items pro rata to their own unit prices and paid to their split policies
+///
+/// `bundle_id` used to mark the only bundle in the chain
+///
+/// `n` is the number of bundles
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+///
+/// return the token ids of the component items in the same order as the bundle
+pub fn buy_bundle(
+    bundle_id: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    payer: &Address,
+) -> Vec<Vec<Vec<u8>>> {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
+    assert_ne!(n, 0);
+    let bundle =
+        database::get::<_, Bundle>(utils::generate_bundle_key(bundle_id)).expect("no bundle");
+    assert!(runtime::timestamp() <= bundle.expired_date);
+    let option = BuyOption::default();
+    let mut item_infos = Vec::with_capacity(bundle.resource_ids.len());
+    let mut weights = Vec::with_capacity(bundle.resource_ids.len());
+    for resource_id in bundle.resource_ids.iter() {
+        let mut item_info = database::get::<_, SellerItemInfo>(
+            utils::generate_seller_item_info_key(resource_id.as_slice()),
+        )
+        .unwrap();
+        assert!(matches!(item_info.item.sale_mode, SaleMode::Fixed));
+        let (_, _, unit_price) = prepare_purchase(
+            resource_id.as_slice(),
+            &mut item_info,
+            n,
+            buyer_account,
+            &option,
+        );
+        item_infos.push(item_info);
+        weights.push(unit_price as U128);
+    }
+    let mut total_weight: U128 = weights.iter().sum();
+    if total_weight == 0 {
+        weights.iter_mut().for_each(|w| *w = 1);
+        total_weight = weights.len() as U128;
+    }
+    let total = bundle.fee.total_amount(&[], n);
+    let mut rest = total;
+    let mut res = Vec::with_capacity(item_infos.len());
+    for (i, item_info) in item_infos.iter().enumerate() {
+        let amount = if i + 1 == item_infos.len() {
+            rest
+        } else {
+            total
+                .checked_mul(weights[i])
+                .unwrap()
+                .checked_div(total_weight)
+                .unwrap()
+        };
+        rest -= amount;
+        let oi = new_order_id(bundle.resource_ids[i].as_slice());
+        let mut fee = bundle.fee.clone();
+        fee.count = u64::try_from(amount).expect("amount overflow");
+        let order = new_order(
+            &oi,
+            buyer_account,
+            payer,
+            n,
+            &fee,
+            u64::try_from(amount.checked_div(n).unwrap()).unwrap(),
+            amount,
+        );
+        assert!(pay_item(
+            &oi,
+            buyer_account,
+            payer,
+            item_info,
+            fee,
+            vec![],
+            1
+        ));
+        res.push(deliver_purchase(item_info, order, false));
+    }
+    EventBuilder::new()
+        .string("buyBundle")
+        .bytearray(bundle_id)
+        .number(n)
+        .address(buyer_account)
+        .address(payer)
+        .number(total)
+        .notify();
+    res
+}
+
+/// subscribe a subscription item
+///
+/// one dtoken of every token template is generated for the buyer, which expires after `periods` periods.
+/// items which require a credential can not be subscribed, because the buyer's ONT ID is not passed
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `periods` is the number of periods paid for
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+pub fn subscribe(
+    resource_id: &[u8],
+    periods: U128,
+    buyer_account: &Address,
+    payer: &Address,
+) -> Vec<Vec<u8>> {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
+    let mut item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    let param = match &item_info.item.sale_mode {
+        SaleMode::Subscription(param) => param.clone(),
+        _ => panic!("not a subscription item"),
+    };
+    assert_ne!(periods, 0);
+    let now = runtime::timestamp();
+    assert_on_sale(resource_id, &item_info.item, now);
+    assert_buyer_allowed(resource_id, &item_info.item, buyer_account, None);
+    record_buyer_purchase(resource_id, &item_info.item, buyer_account, 1, now);
+    item_info.item.sold = item_info.item.sold.checked_add(1).unwrap();
+    assert!(item_info.item.sold <= item_info.item.stocks);
+    let (fee, tiers) = item_info.item.pricing(now);
+    let oi = new_order_id(resource_id);
+    let mut order = new_order(
+        &oi,
+        buyer_account,
+        payer,
+        periods,
+        &fee,
+        fee.unit_price(tiers.as_slice(), periods),
+        fee.total_amount(tiers.as_slice(), periods),
+    );
+    set_escrow_status(&mut order, &item_info);
+    assert!(pay_item(
+        &oi,
+        buyer_account,
+        payer,
+        &item_info,
+        fee,
+        tiers,
+        periods
+    ));
+    database::put(
+        utils::generate_seller_item_info_key(resource_id),
+        &item_info,
+    );
+    let duration = param
+        .period
+        .checked_mul(u64::try_from(periods).unwrap())
+        .unwrap();
+    let valid_until = now.checked_add(duration).unwrap();
+    let token_ids = generate_dtoken_with_validity(
+        &item_info.resource_ddo.dtoken_contract_address,
+        item_info.item.token_template_ids.as_slice(),
+        buyer_account,
+        1,
+        valid_until,
+    );
+    order.token_ids = token_ids.clone();
+    record_order(&order);
+    EventBuilder::new()
+        .string("subscribe")
+        .bytearray(resource_id)
+        .number(periods)
+        .address(buyer_account)
+        .address(payer)
+        .number(valid_until as U128)
+        .notify();
+    token_ids
+}
+
+/// renew the dtokens of a subscription item
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `token_ids` are the dtokens generated by `subscribe`, in the same order as the token templates of the item
+///
+/// `periods` is the number of periods paid for
+///
+/// `payer` is the address who pay the fee, need this address signature
+pub fn renew(resource_id: &[u8], token_ids: Vec<Vec<u8>>, periods: U128, payer: &Address) -> bool {
+    assert!(runtime::check_witness(payer));
+    let item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    let param = match &item_info.item.sale_mode {
+        SaleMode::Subscription(param) => param.clone(),
+        _ => panic!("not a subscription item"),
+    };
+    assert_ne!(periods, 0);
+    let now = runtime::timestamp();
+    assert_on_sale(resource_id, &item_info.item, now);
+    let (fee, tiers) = item_info.item.pricing(now);
+    let oi = new_order_id(resource_id);
+    let mut order = new_order(
+        &oi,
+        payer,
+        payer,
+        periods,
+        &fee,
+        fee.unit_price(tiers.as_slice(), periods),
+        fee.total_amount(tiers.as_slice(), periods),
+    );
+    set_escrow_status(&mut order, &item_info);
+    assert!(pay_item(&oi, payer, payer, &item_info, fee, tiers, periods));
+    let duration = param
+        .period
+        .checked_mul(u64::try_from(periods).unwrap())
+        .unwrap();
+    extend_dtoken_validity(
+        &item_info.resource_ddo.dtoken_contract_address,
+        item_info.item.token_template_ids.as_slice(),
+        token_ids.as_slice(),
+        duration,
+    );
+    order.token_ids = token_ids;
+    record_order(&order);
+    EventBuilder::new()
+        .string("renew")
+        .bytearray(resource_id)
+        .number(periods)
+        .address(payer)
+        .notify();
+    true
+}
+
+/// buy_dtoken_reward
 ///
 /// This method can only be called for items that the fee.count is 0, The buyer can reward the seller with any number of tokens.
 ///
@@ -411,79 +2256,644 @@ pub fn buy_dtoken_reward(
         database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
             .unwrap();
     assert!(item_info.item.fee.count == 0);
+    assert!(matches!(item_info.item.sale_mode, SaleMode::Fixed));
     let now = runtime::timestamp();
-    assert!(now < item_info.item.expired_date);
+    assert_on_sale(resource_id, &item_info.item, now);
+    assert_buyer_allowed(resource_id, &item_info.item, buyer_account, None);
+    record_buyer_purchase(resource_id, &item_info.item, buyer_account, n, now);
 
     assert!(item_info.item.sold < item_info.item.stocks);
     item_info.item.sold = n.checked_add(item_info.item.sold as U128).unwrap() as u64;
     assert!(item_info.item.sold <= item_info.item.stocks);
-    let oi = OrderId {
-        item_id: resource_id.to_vec(),
-        tx_hash: current_txhash(),
+    let oi = new_order_id(resource_id);
+    let mut fee = item_info.item.fee.clone();
+    fee.count = unit_price as u64;
+    let mut order = new_order(
+        &oi,
+        buyer_account,
+        payer,
+        n,
+        &fee,
+        fee.count,
+        fee.total_amount(&[], n),
+    );
+    set_escrow_status(&mut order, &item_info);
+    assert!(pay_item(
+        &oi,
+        buyer_account,
+        payer,
+        &item_info,
+        fee,
+        vec![],
+        n
+    ));
+    database::put(
+        utils::generate_seller_item_info_key(resource_id),
+        &item_info,
+    );
+    let res = generate_dtoken(
+        &item_info.resource_ddo.dtoken_contract_address,
+        item_info.item.token_template_ids.as_slice(),
+        buyer_account,
+        n,
+    );
+    order.token_ids = res.clone();
+    record_order(&order);
+    EventBuilder::new()
+        .string("buyDTokenReward")
+        .bytearray(resource_id)
+        .number(n)
+        .address(buyer_account)
+        .address(payer)
+        .number(unit_price)
+        .notify();
+    res
+}
+
+/// bid for an auction item
+///
+/// the bid price is escrowed in the marketplace contract, the previous highest bid is refunded to its payer.
+/// bids for an item which requires a credential are rejected, because the bidder's ONT ID is not passed
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `bidder` will receive the dtokens if the bid wins, need this address signature
+///
+/// `payer` is the address who pay the bid price, need this address signature
+///
+/// `price` is the price for the whole lot, must reach the reserve price and exceed the current bid by `min_increment`
+pub fn bid(resource_id: &[u8], bidder: &Address, payer: &Address, price: U128) -> bool {
+    assert!(runtime::check_witness(bidder) && runtime::check_witness(payer));
+    let item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    let param = match &item_info.item.sale_mode {
+        SaleMode::Auction(param) => param.clone(),
+        _ => panic!("not an auction item"),
+    };
+    let now = runtime::timestamp();
+    assert!(!is_item_paused(resource_id));
+    assert_buyer_allowed(resource_id, &item_info.item, bidder, None);
+    assert!(now >= param.start_time && now < param.end_time);
+    assert!(item_info.item.sold < item_info.item.stocks);
+    let price = u64::try_from(price).expect("price overflow");
+    assert!(price >= param.reserve_price);
+    let self_addr = address();
+    let key = utils::generate_auction_bid_key(resource_id);
+    if let Some(prev) = database::get::<_, Bid>(key.as_slice()) {
+        assert!(price >= prev.price.checked_add(param.min_increment).unwrap());
+        assert!(transfer_token(
+            &self_addr,
+            &prev.payer,
+            prev.price as U128,
+            &item_info.item.fee
+        ));
+    }
+    assert!(transfer_token(
+        payer,
+        &self_addr,
+        price as U128,
+        &item_info.item.fee
+    ));
+    database::put(
+        key,
+        Bid {
+            bidder: bidder.clone(),
+            payer: payer.clone(),
+            price,
+        },
+    );
+    EventBuilder::new()
+        .string("bid")
+        .bytearray(resource_id)
+        .address(bidder)
+        .address(payer)
+        .number(price as U128)
+        .notify();
+    true
+}
+
+/// query the current highest bid of an auction item, return empty bytes if there is no bid
+pub fn get_auction_bid(resource_id: &[u8]) -> Vec<u8> {
+    runtime::storage_read(utils::generate_auction_bid_key(resource_id).as_slice())
+        .unwrap_or_default()
+}
+
+/// settle an auction after it ends, anyone can invoke this method
+///
+/// the escrowed price of the highest bid is paid to the seller and the remaining stocks are generated for the bidder
+/// by this marketplace, so the bidder's signature is not needed
+///
+/// `resource_id` used to mark the only commodity in the chain
+pub fn settle_auction(resource_id: &[u8]) -> Vec<Vec<u8>> {
+    let mut item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    let param = match &item_info.item.sale_mode {
+        SaleMode::Auction(param) => param.clone(),
+        _ => panic!("not an auction item"),
+    };
+    assert!(runtime::timestamp() >= param.end_time);
+    let key = utils::generate_auction_bid_key(resource_id);
+    let highest = database::get::<_, Bid>(key.as_slice()).expect("no bid");
+    database::delete(key);
+    let n = item_info
+        .item
+        .stocks
+        .checked_sub(item_info.item.sold)
+        .unwrap();
+    item_info.item.sold = item_info.item.stocks;
+    database::put(
+        utils::generate_seller_item_info_key(resource_id),
+        &item_info,
+    );
+    let oi = new_order_id(resource_id);
+    let mut fee = item_info.item.fee.clone();
+    fee.count = highest.price;
+    let mut order = new_order(
+        &oi,
+        &highest.bidder,
+        &highest.payer,
+        n as U128,
+        &fee,
+        highest.price.checked_div(n).unwrap(),
+        highest.price as U128,
+    );
+    assert!(transfer_fee(
+        &oi,
+        &address(),
+        item_info.resource_ddo.accountant_contract_address.clone(),
+        &item_info
+            .resource_ddo
+            .split_policy_contract_address
+            .unwrap_or(get_split_policy_contract()),
+        fee,
+        vec![],
+        1
+    ));
+    let token_ids = generate_dtoken_for_other(
+        &item_info.resource_ddo.dtoken_contract_address,
+        item_info.item.token_template_ids.as_slice(),
+        &highest.bidder,
+        n as U128,
+    );
+    order.token_ids = token_ids.clone();
+    record_order(&order);
+    EventBuilder::new()
+        .string("settleAuction")
+        .bytearray(resource_id)
+        .number(n as U128)
+        .address(&highest.bidder)
+        .number(highest.price as U128)
+        .notify();
+    token_ids
+}
+
+/// commit to buy a pre-sale item, the fee is escrowed in the marketplace contract until the deadline
+///
+/// commits to an item which requires a credential are rejected, because the buyer's ONT ID is not passed
+///
+/// `resource_id` used to mark the only commodity in the chain
+///
+/// `n` is the number of purchases, a buyer can commit several times with the same payer
+///
+/// `buyer_account` is buyer address, need this address signature
+///
+/// `payer` is the address who pay the fee, need this address signature
+pub fn commit_presale(
+    resource_id: &[u8],
+    n: U128,
+    buyer_account: &Address,
+    payer: &Address,
+) -> bool {
+    assert!(runtime::check_witness(buyer_account) && runtime::check_witness(payer));
+    assert_ne!(n, 0);
+    let mut item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    let param = match &item_info.item.sale_mode {
+        SaleMode::Presale(param) => param.clone(),
+        _ => panic!("not a pre-sale item"),
+    };
+    let now = runtime::timestamp();
+    assert!(now < param.deadline);
+    assert_on_sale(resource_id, &item_info.item, now);
+    assert_buyer_allowed(resource_id, &item_info.item, buyer_account, None);
+    record_buyer_purchase(resource_id, &item_info.item, buyer_account, n, now);
+    let sold = n.checked_add(item_info.item.sold as U128).unwrap();
+    item_info.item.sold = u64::try_from(sold).expect("sold overflow");
+    assert!(item_info.item.sold <= item_info.item.stocks);
+    let (fee, tiers) = item_info.item.pricing(now);
+    let amount = fee.total_amount(tiers.as_slice(), n);
+    assert!(transfer_token(payer, &address(), amount, &fee));
+    database::put(
+        utils::generate_seller_item_info_key(resource_id),
+        &item_info,
+    );
+
+    let mut state = database::get::<_, PresaleState>(utils::generate_presale_key(resource_id))
+        .unwrap_or(PresaleState::default());
+    state.total = state.total.checked_add(amount).unwrap();
+    let pledge_key = utils::generate_pledge_key(resource_id, buyer_account);
+    let pledge = match database::get::<_, Pledge>(pledge_key.as_slice()) {
+        Some(mut pledge) => {
+            assert!(&pledge.payer == payer);
+            pledge.n = pledge.n.checked_add(n).unwrap();
+            pledge.amount = pledge.amount.checked_add(amount).unwrap();
+            pledge
+        }
+        None => {
+            state.pledges += 1;
+            Pledge {
+                payer: payer.clone(),
+                n,
+                amount,
+            }
+        }
+    };
+    database::put(pledge_key, &pledge);
+    database::put(utils::generate_presale_key(resource_id), &state);
+    EventBuilder::new()
+        .string("commitPresale")
+        .bytearray(resource_id)
+        .number(n)
+        .address(buyer_account)
+        .address(payer)
+        .number(amount)
+        .notify();
+    true
+}
+
+/// finalize a pre-sale after the deadline if the minimum sold count is reached, anyone can invoke this method
+///
+/// the escrowed fee is paid to the split policy of the item, the buyers claim their dtokens by `claim_presale`
+pub fn finalize_presale(resource_id: &[u8]) -> bool {
+    let item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    let param = match &item_info.item.sale_mode {
+        SaleMode::Presale(param) => param.clone(),
+        _ => panic!("not a pre-sale item"),
     };
+    assert!(runtime::timestamp() >= param.deadline);
+    assert!(item_info.item.sold >= param.min_sold);
+    let key = utils::generate_presale_key(resource_id);
+    let mut state = database::get::<_, PresaleState>(key.as_slice()).expect("no pledge");
+    assert!(!state.finalized);
+    state.finalized = true;
+    database::put(key, &state);
+    let oi = new_order_id(resource_id);
     let mut fee = item_info.item.fee.clone();
-    fee.count = unit_price as u64;
+    fee.count = u64::try_from(state.total).expect("total overflow");
     assert!(transfer_fee(
         &oi,
-        payer,
+        &address(),
         item_info.resource_ddo.accountant_contract_address.clone(),
         &item_info
             .resource_ddo
             .split_policy_contract_address
             .unwrap_or(get_split_policy_contract()),
         fee,
-        n
+        vec![],
+        1
     ));
-    database::put(
-        utils::generate_seller_item_info_key(resource_id),
-        &item_info,
-    );
-    let res = generate_dtoken(
+    EventBuilder::new()
+        .string("finalizePresale")
+        .bytearray(resource_id)
+        .number(item_info.item.sold as U128)
+        .number(state.total)
+        .notify();
+    true
+}
+
+/// claim the pledge of a buyer after the deadline, anyone can invoke this method
+///
+/// the dtokens are generated for the buyer by this marketplace if the pre-sale is finalized, so the buyer's signature
+/// is not needed, otherwise the fee is refunded to the payer if the minimum sold count is not reached
+///
+/// return the generated token ids, empty if the fee is refunded
+pub fn claim_presale(resource_id: &[u8], buyer_account: &Address) -> Vec<Vec<u8>> {
+    let item_info =
+        database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id))
+            .unwrap();
+    let param = match &item_info.item.sale_mode {
+        SaleMode::Presale(param) => param.clone(),
+        _ => panic!("not a pre-sale item"),
+    };
+    assert!(runtime::timestamp() >= param.deadline);
+    let key = utils::generate_presale_key(resource_id);
+    let mut state = database::get::<_, PresaleState>(key.as_slice()).expect("no pledge");
+    let pledge_key = utils::generate_pledge_key(resource_id, buyer_account);
+    let pledge = database::get::<_, Pledge>(pledge_key.as_slice()).expect("no pledge");
+    database::delete(pledge_key);
+    state.pledges -= 1;
+    database::put(key, &state);
+    if !state.finalized {
+        assert!(item_info.item.sold < param.min_sold);
+        let fee = &item_info.item.fee;
+        assert!(transfer_token(
+            &address(),
+            &pledge.payer,
+            pledge.amount,
+            fee
+        ));
+        EventBuilder::new()
+            .string("refundPresale")
+            .bytearray(resource_id)
+            .address(buyer_account)
+            .address(&pledge.payer)
+            .number(pledge.amount)
+            .notify();
+        return vec![];
+    }
+    let token_ids = generate_dtoken_for_other(
         &item_info.resource_ddo.dtoken_contract_address,
         item_info.item.token_template_ids.as_slice(),
         buyer_account,
-        n,
+        pledge.n,
+    );
+    let oi = new_order_id(resource_id);
+    let mut order = new_order(
+        &oi,
+        buyer_account,
+        &pledge.payer,
+        pledge.n,
+        &item_info.item.fee,
+        u64::try_from(pledge.amount.checked_div(pledge.n).unwrap()).unwrap(),
+        pledge.amount,
     );
+    order.token_ids = token_ids.clone();
+    record_order(&order);
     EventBuilder::new()
-        .string("buyDTokenReward")
+        .string("claimPresale")
         .bytearray(resource_id)
-        .number(n)
+        .number(pledge.n)
         .address(buyer_account)
-        .address(payer)
-        .number(unit_price)
         .notify();
-    res
+    token_ids
+}
+
+/// query the pledge of a buyer in a pre-sale, return empty bytes if there is no pledge
+pub fn get_pledge(resource_id: &[u8], buyer_account: &Address) -> Vec<u8> {
+    runtime::storage_read(utils::generate_pledge_key(resource_id, buyer_account).as_slice())
+        .unwrap_or_default()
+}
+
+/// query the escrowed payments of a pre-sale item, return empty bytes if there is no pledge
+pub fn get_presale_state(resource_id: &[u8]) -> Vec<u8> {
+    runtime::storage_read(utils::generate_presale_key(resource_id).as_slice()).unwrap_or_default()
+}
+
+// inner method
+//
+// id of a new order of `resource_id`, the serialization result of OrderId followed by the sequence
+// of the order in the current transaction, so that several orders of the same item in one transaction
+// have different ids. the sequence is omitted for the first order of a transaction
+fn new_order_id(resource_id: &[u8]) -> Vec<u8> {
+    let tx_hash = current_txhash();
+    let seq = match database::get::<_, (H256, u64)>(KEY_ORDER_SEQ) {
+        Some((last_tx_hash, last_seq)) if last_tx_hash == tx_hash => last_seq + 1,
+        _ => 0,
+    };
+    database::put(KEY_ORDER_SEQ, (&tx_hash, seq));
+    let oi = OrderId {
+        item_id: resource_id.to_vec(),
+        tx_hash,
+    };
+    let mut sink = Sink::new(64);
+    sink.write(&oi);
+    if seq != 0 {
+        sink.write(seq);
+    }
+    sink.bytes().to_vec()
+}
+
+// inner method
+//
+// order of a purchase paid in the currency of `fee`, the token ids are filled in after they are generated
+fn new_order(
+    oi: &[u8],
+    buyer_account: &Address,
+    payer: &Address,
+    n: U128,
+    fee: &Fee,
+    unit_price: u64,
+    amount: U128,
+) -> Order {
+    let mut fee = fee.clone();
+    fee.count = unit_price;
+    Order {
+        order_id: oi.to_vec(),
+        resource_id: OrderId::from_bytes(oi).item_id,
+        buyer: buyer_account.clone(),
+        payer: payer.clone(),
+        n,
+        fee,
+        amount,
+        token_ids: vec![],
+        status: OrderStatus::Paid,
+        timestamp: runtime::timestamp(),
+        accountant: None,
+    }
+}
+
+// inner method
+//
+// mark the order escrowed if the fee is held by the accountant contract during the dispute window of the item
+fn set_escrow_status(order: &mut Order, item_info: &SellerItemInfo) {
+    if item_info.item.dispute_window != 0 {
+        order.status = OrderStatus::Escrowed;
+        order.accountant = item_info.resource_ddo.accountant_contract_address;
+    }
+}
+
+// inner method
+//
+// save the order and append it to the order lists of the buyer and the item
+fn record_order(order: &Order) {
+    let key = utils::generate_order_key(order.order_id.as_slice());
+    assert!(runtime::storage_read(key.as_slice()).is_none());
+    database::put(key, order);
+    append_order_index(
+        utils::generate_buyer_order_count_key(&order.buyer),
+        order.order_id.as_slice(),
+    );
+    append_order_index(
+        utils::generate_item_order_count_key(order.resource_id.as_slice()),
+        order.order_id.as_slice(),
+    );
+}
+
+fn append_order_index(count_key: Vec<u8>, order_id: &[u8]) {
+    let count = database::get::<_, u64>(count_key.as_slice()).unwrap_or(0);
+    database::put(
+        utils::generate_order_index_key(count_key.as_slice(), count),
+        order_id,
+    );
+    database::put(count_key, count + 1);
+}
+
+fn get_order_page(count_key: Vec<u8>, start: u64, limit: u64) -> Vec<Order> {
+    let count = database::get::<_, u64>(count_key.as_slice()).unwrap_or(0);
+    let end = count.min(start.saturating_add(limit.min(MAX_PAGE_SIZE)));
+    let mut orders = vec![];
+    for i in start..end {
+        let order_id =
+            database::get::<_, Vec<u8>>(utils::generate_order_index_key(count_key.as_slice(), i))
+                .unwrap();
+        orders.push(
+            database::get::<_, Order>(utils::generate_order_key(order_id.as_slice())).unwrap(),
+        );
+    }
+    orders
+}
+
+// inner method
+//
+// pay for an item, the fee is held by the accountant contract if the item has a dispute window
+fn pay_item(
+    oi: &[u8],
+    buyer_account: &Address,
+    payer: &Address,
+    item_info: &SellerItemInfo,
+    fee: Fee,
+    tiers: Vec<PriceTier>,
+    n: U128,
+) -> bool {
+    let split_contract_address = item_info
+        .resource_ddo
+        .split_policy_contract_address
+        .unwrap_or(get_split_policy_contract());
+    if item_info.item.dispute_window == 0 {
+        return transfer_fee(
+            oi,
+            payer,
+            item_info.resource_ddo.accountant_contract_address,
+            &split_contract_address,
+            fee,
+            tiers,
+            n,
+        );
+    }
+    let accountant_addr = item_info
+        .resource_ddo
+        .accountant_contract_address
+        .expect("escrow needs accountant contract");
+    verify_result(wasm::call_contract(
+        &accountant_addr,
+        (
+            "transferAmountEscrow",
+            (
+                oi,
+                payer,
+                buyer_account,
+                &item_info.resource_ddo.manager,
+                &split_contract_address,
+                fee,
+                n,
+                tiers,
+                item_info.item.dispute_window,
+            ),
+        ),
+    ));
+    true
+}
+
+// inner method
+//
+// pay a secondary sale, the royalty goes to the split policy of the item and the rest goes to the reseller
+fn pay_resale(
+    oi: &[u8],
+    payer: &Address,
+    reseller: &Address,
+    item_info: Option<&SellerItemInfo>,
+    fee: &Fee,
+    amt: U128,
+) -> bool {
+    let mut royalty_amt = 0;
+    if let Some(item_info) = item_info {
+        royalty_amt = amt
+            .checked_mul(item_info.item.royalty as U128)
+            .unwrap()
+            .checked_div(MAX_PERCENTAGE)
+            .unwrap();
+        if royalty_amt != 0 {
+            let mut royalty_fee = fee.clone();
+            royalty_fee.count = u64::try_from(royalty_amt).expect("royalty overflow");
+            assert!(transfer_fee(
+                oi,
+                payer,
+                item_info.resource_ddo.accountant_contract_address,
+                &item_info
+                    .resource_ddo
+                    .split_policy_contract_address
+                    .unwrap_or(get_split_policy_contract()),
+                royalty_fee,
+                vec![],
+                1
+            ));
+        }
+    }
+    let reseller_amt = amt.checked_sub(royalty_amt).unwrap();
+    if reseller_amt != 0 {
+        assert!(transfer_token(payer, reseller, reseller_amt, fee));
+    }
+    true
 }
 
 // inner method
 fn transfer_fee(
-    oi: &OrderId,
+    oi: &[u8],
     payer: &Address,
     accountant_contract_address: Option<Address>,
     split_contract_address: &Address,
     fee: Fee,
+    tiers: Vec<PriceTier>,
     n: U128,
 ) -> bool {
     let res = match accountant_contract_address {
-        Some(accountant_addr) => wasm::call_contract(
+        Some(accountant_addr) if tiers.is_empty() => wasm::call_contract(
             &accountant_addr,
             (
                 "transferAmount",
-                (oi.to_bytes(), payer, split_contract_address, fee, n),
+                (oi, payer, split_contract_address, fee, n),
+            ),
+        ),
+        Some(accountant_addr) => wasm::call_contract(
+            &accountant_addr,
+            (
+                "transferAmountWithTiers",
+                (oi, payer, split_contract_address, fee, n, tiers),
             ),
         ),
         _ => {
-            let amt = n.checked_mul(fee.count as U128).unwrap();
-            wasm::call_contract(
-                split_contract_address,
-                ("transferWithdraw", (payer, oi.item_id.as_slice(), amt)),
-            )
+            let amt = fee.total_amount(tiers.as_slice(), n);
+            let item_id = OrderId::from_bytes(oi).item_id;
+            split_transfer_withdraw(split_contract_address, payer, item_id.as_slice(), amt, &fee)
         }
     };
     verify_result(res);
     true
 }
 
+fn transfer_token(from: &Address, to: &Address, amt: U128, fee: &Fee) -> bool {
+    match fee.contract_type {
+        TokenType::ONG => {
+            assert!(ong::transfer(from, to, amt));
+        }
+        TokenType::ONT => {
+            assert!(ont::transfer(from, to, amt));
+        }
+        TokenType::OEP4 => {
+            verify_result(wasm::call_contract(
+                &fee.contract_addr,
+                ("transfer", (from, to, amt)),
+            ));
+        }
+    }
+    true
+}
+
 fn verify_result(res: Option<Vec<u8>>) {
     if let Some(r) = res {
         let mut source = Source::new(r.as_slice());
@@ -494,6 +2904,17 @@ fn verify_result(res: Option<Vec<u8>>) {
     }
 }
 
+// inner method
+//
+// read the arguments of `dtokenSellerPublish` and `update`, see `dtoken_seller_publish`
+fn read_publish_args<'a>(source: &mut Source<'a>) -> (&'a [u8], ResourceDDO, DTokenItem, &'a [u8]) {
+    let (resource_id, resource_ddo) = source.read().unwrap();
+    let mut item = DTokenItem::decode_base(source).unwrap();
+    let split_policy_param_bytes = source.read().unwrap();
+    item.decode_ext(source).unwrap();
+    (resource_id, resource_ddo, item, split_policy_param_bytes)
+}
+
 #[no_mangle]
 pub fn invoke() {
     let input = runtime::input();
@@ -505,6 +2926,20 @@ pub fn invoke() {
             let new_admin = source.read().unwrap();
             sink.write(update_admin(&new_admin));
         }
+        b"setRegistryContract" => {
+            let new_addr = source.read().unwrap();
+            sink.write(set_registry_contract(new_addr));
+        }
+        b"getRegistryContract" => {
+            sink.write(get_registry_contract());
+        }
+        b"registerMarketplace" => {
+            let (endpoint, fee_rate) = source.read().unwrap();
+            sink.write(register_marketplace(endpoint, fee_rate));
+        }
+        b"isRegistered" => {
+            sink.write(is_registered());
+        }
         b"getAdmin" => {
             sink.write(get_admin());
         }
@@ -524,13 +2959,20 @@ pub fn invoke() {
         b"getDTokenContract" => {
             sink.write(get_dtoken_contract());
         }
+        b"setOracleContract" => {
+            let new_addr = source.read().unwrap();
+            sink.write(set_oracle_contract(new_addr));
+        }
+        b"getOracleContract" => {
+            sink.write(get_oracle_contract());
+        }
         b"migrate" => {
             let (code, vm_type, name, version, author, email, desc) = source.read().unwrap();
             sink.write(CONTRACT_COMMON.migrate(code, vm_type, name, version, author, email, desc));
         }
         b"update" => {
             let (resource_id, resource_ddo, item, split_policy_param_bytes) =
-                source.read().unwrap();
+                read_publish_args(&mut source);
             sink.write(update(
                 resource_id,
                 resource_ddo,
@@ -544,7 +2986,7 @@ pub fn invoke() {
         }
         b"dtokenSellerPublish" => {
             let (resource_id, resource_ddo, item, split_policy_param_bytes) =
-                source.read().unwrap();
+                read_publish_args(&mut source);
             sink.write(dtoken_seller_publish(
                 resource_id,
                 resource_ddo,
@@ -552,6 +2994,70 @@ pub fn invoke() {
                 split_policy_param_bytes,
             ));
         }
+        b"dtokenSellerPublishMulti" => {
+            let items = source.read().unwrap();
+            sink.write(dtoken_seller_publish_multi(items));
+        }
+        b"dtokenSellerPublishEndorsed" => {
+            let (resource_id, resource_ddo, item, split_policy_param_bytes, endorsement, sig) =
+                source.read().unwrap();
+            sink.write(dtoken_seller_publish_endorsed(
+                resource_id,
+                resource_ddo,
+                item,
+                split_policy_param_bytes,
+                endorsement,
+                sig,
+            ));
+        }
+        b"isEndorsementNonceUsed" => {
+            let nonce = source.read().unwrap();
+            sink.write(is_endorsement_nonce_used(nonce));
+        }
+        b"updatePrice" => {
+            let (resource_id, count, price_tiers) = source.read().unwrap();
+            sink.write(update_price(resource_id, count, price_tiers));
+        }
+        b"addStocks" => {
+            let (resource_id, n) = source.read().unwrap();
+            sink.write(add_stocks(resource_id, n));
+        }
+        b"extendExpiry" => {
+            let (resource_id, expired_date) = source.read().unwrap();
+            sink.write(extend_expiry(resource_id, expired_date));
+        }
+        b"updateTokenTemplates" => {
+            let (resource_id, token_template_ids) = source.read().unwrap();
+            sink.write(update_token_templates(resource_id, token_template_ids));
+        }
+        b"addAccessList" => {
+            let (resource_id, buyers) = source.read().unwrap();
+            sink.write(add_access_list(resource_id, buyers));
+        }
+        b"removeAccessList" => {
+            let (resource_id, buyers) = source.read().unwrap();
+            sink.write(remove_access_list(resource_id, buyers));
+        }
+        b"isInAccessList" => {
+            let (resource_id, buyer) = source.read().unwrap();
+            sink.write(is_in_access_list(resource_id, buyer));
+        }
+        b"getBuyerPurchase" => {
+            let (resource_id, buyer) = source.read().unwrap();
+            sink.write(get_buyer_purchase(resource_id, buyer));
+        }
+        b"pauseItem" => {
+            let resource_id = source.read().unwrap();
+            sink.write(pause_item(resource_id));
+        }
+        b"resumeItem" => {
+            let resource_id = source.read().unwrap();
+            sink.write(resume_item(resource_id));
+        }
+        b"isItemPaused" => {
+            let resource_id = source.read().unwrap();
+            sink.write(is_item_paused(resource_id));
+        }
         b"getSellerItemInfo" => {
             let resource_id = source.read().unwrap();
             sink.write(get_seller_item_info(resource_id))
@@ -565,6 +3071,121 @@ pub fn invoke() {
                 reseller_account,
             ));
         }
+        b"postSellOrder" => {
+            let (seller, resource_id, token_id, amount, price, expired_date) =
+                source.read().unwrap();
+            sink.write(post_sell_order(
+                seller,
+                resource_id,
+                token_id,
+                amount,
+                price,
+                expired_date,
+            ));
+        }
+        b"fillSellOrder" => {
+            let (order_id, n, buyer_account, payer) = source.read().unwrap();
+            sink.write(fill_sell_order(order_id, n, buyer_account, payer));
+        }
+        b"cancelSellOrder" => {
+            let order_id = source.read().unwrap();
+            sink.write(cancel_sell_order(order_id));
+        }
+        b"getSellOrder" => {
+            let order_id = source.read().unwrap();
+            sink.write(get_sell_order(order_id));
+        }
+        b"getItemCount" => {
+            sink.write(get_item_count());
+        }
+        b"getItems" => {
+            let (start, limit) = source.read().unwrap();
+            sink.write(get_items(start, limit));
+        }
+        b"getManagerItemCount" => {
+            let manager = source.read().unwrap();
+            sink.write(get_manager_item_count(manager));
+        }
+        b"getItemsByManager" => {
+            let (manager, start, limit) = source.read().unwrap();
+            sink.write(get_items_by_manager(manager, start, limit));
+        }
+        b"getTemplateItemCount" => {
+            let token_template_id = source.read().unwrap();
+            sink.write(get_template_item_count(token_template_id));
+        }
+        b"getItemsByTokenTemplate" => {
+            let (token_template_id, start, limit) = source.read().unwrap();
+            sink.write(get_items_by_token_template(token_template_id, start, limit));
+        }
+        b"indexItems" => {
+            let resource_ids = source.read().unwrap();
+            sink.write(index_items(resource_ids));
+        }
+        b"getOrder" => {
+            let order_id = source.read().unwrap();
+            sink.write(get_order(order_id));
+        }
+        b"updateOrderStatus" => {
+            let (order_id, refunded) = source.read().unwrap();
+            sink.write(update_order_status(order_id, refunded));
+        }
+        b"getBuyerOrderCount" => {
+            let buyer = source.read().unwrap();
+            sink.write(get_buyer_order_count(buyer));
+        }
+        b"getBuyerOrders" => {
+            let (buyer, start, limit) = source.read().unwrap();
+            sink.write(get_buyer_orders(buyer, start, limit));
+        }
+        b"getItemOrderCount" => {
+            let resource_id = source.read().unwrap();
+            sink.write(get_item_order_count(resource_id));
+        }
+        b"getItemOrders" => {
+            let (resource_id, start, limit) = source.read().unwrap();
+            sink.write(get_item_orders(resource_id, start, limit));
+        }
+        b"publishBundle" => {
+            let (bundle_id, resource_ids, fee, expired_date) = source.read().unwrap();
+            sink.write(publish_bundle(bundle_id, resource_ids, fee, expired_date));
+        }
+        b"deleteBundle" => {
+            let bundle_id = source.read().unwrap();
+            sink.write(delete_bundle(bundle_id));
+        }
+        b"getBundle" => {
+            let bundle_id = source.read().unwrap();
+            sink.write(get_bundle(bundle_id));
+        }
+        b"buyBundle" => {
+            let (bundle_id, n, buyer, payer) = source.read().unwrap();
+            sink.write(buy_bundle(bundle_id, n, buyer, payer));
+        }
+        b"commitPresale" => {
+            let (resource_id, n, buyer, payer) = source.read().unwrap();
+            sink.write(commit_presale(resource_id, n, buyer, payer));
+        }
+        b"finalizePresale" => {
+            let resource_id = source.read().unwrap();
+            sink.write(finalize_presale(resource_id));
+        }
+        b"claimPresale" => {
+            let (resource_id, buyer) = source.read().unwrap();
+            sink.write(claim_presale(resource_id, buyer));
+        }
+        b"getPledge" => {
+            let (resource_id, buyer) = source.read().unwrap();
+            sink.write(get_pledge(resource_id, buyer));
+        }
+        b"getPresaleState" => {
+            let resource_id = source.read().unwrap();
+            sink.write(get_presale_state(resource_id));
+        }
+        b"checkout" => {
+            let (resource_ids, ns, buyer, payer) = source.read().unwrap();
+            sink.write(checkout(resource_ids, ns, buyer, payer));
+        }
         b"buyDTokens" => {
             let (resource_ids, ns, buyer, payer) = source.read().unwrap();
             sink.write(buy_dtokens(resource_ids, ns, buyer, payer));
@@ -573,6 +3194,112 @@ pub fn invoke() {
             let (resource_id, n, buyer_account, payer) = source.read().unwrap();
             sink.write(buy_dtoken(resource_id, n, buyer_account, payer));
         }
+        b"buyDTokenWithMaxPrice" => {
+            let (resource_id, n, buyer_account, payer, max_unit_price) = source.read().unwrap();
+            sink.write(buy_dtoken_with_max_price(
+                resource_id,
+                n,
+                buyer_account,
+                payer,
+                max_unit_price,
+            ));
+        }
+        b"setSigningKey" => {
+            let (account, pub_key) = source.read().unwrap();
+            sink.write(set_signing_key(account, pub_key));
+        }
+        b"getSigningKey" => {
+            let account = source.read().unwrap();
+            sink.write(get_signing_key(account));
+        }
+        b"buyDTokenWithCurrency" => {
+            let (resource_id, n, buyer_account, payer, fee_index, max_unit_price) =
+                source.read().unwrap();
+            sink.write(buy_dtoken_with_currency(
+                resource_id,
+                n,
+                buyer_account,
+                payer,
+                fee_index,
+                max_unit_price,
+            ));
+        }
+        b"buyDTokenWithOracle" => {
+            let (resource_id, n, buyer_account, payer, fee_index, max_price_age, max_unit_price) =
+                source.read().unwrap();
+            sink.write(buy_dtoken_with_oracle(
+                resource_id,
+                n,
+                buyer_account,
+                payer,
+                fee_index,
+                max_price_age,
+                max_unit_price,
+            ));
+        }
+        b"buyDTokenWithCredential" => {
+            let (resource_id, n, buyer_account, payer, ont_id, key_index) = source.read().unwrap();
+            sink.write(buy_dtoken_with_credential(
+                resource_id,
+                n,
+                buyer_account,
+                payer,
+                ont_id,
+                key_index,
+            ));
+        }
+        b"buyDTokenWithCoupon" => {
+            let (resource_id, n, buyer_account, payer, coupon, sig) = source.read().unwrap();
+            sink.write(buy_dtoken_with_coupon(
+                resource_id,
+                n,
+                buyer_account,
+                payer,
+                coupon,
+                sig,
+            ));
+        }
+        b"buyDTokenByIntent" => {
+            let (intent, sig, payer) = source.read().unwrap();
+            sink.write(buy_dtoken_by_intent(intent, sig, payer));
+        }
+        b"buyDTokenFromListing" => {
+            let (listing, seller_sig, mp_sig, n, buyer, payer, max_unit_price) =
+                source.read().unwrap();
+            sink.write(buy_dtoken_from_listing(
+                listing,
+                seller_sig,
+                mp_sig,
+                n,
+                buyer,
+                payer,
+                max_unit_price,
+            ));
+        }
+        b"revokeListing" => {
+            let (seller, nonce) = source.read().unwrap();
+            sink.write(revoke_listing(seller, nonce));
+        }
+        b"isListingRevoked" => {
+            let (seller, nonce) = source.read().unwrap();
+            sink.write(is_listing_revoked(seller, nonce));
+        }
+        b"isIntentNonceUsed" => {
+            let (buyer, nonce) = source.read().unwrap();
+            sink.write(is_intent_nonce_used(buyer, nonce));
+        }
+        b"getCouponRedeemed" => {
+            let (resource_id, coupon_id) = source.read().unwrap();
+            sink.write(get_coupon_redeemed(resource_id, coupon_id));
+        }
+        b"subscribe" => {
+            let (resource_id, periods, buyer_account, payer) = source.read().unwrap();
+            sink.write(subscribe(resource_id, periods, buyer_account, payer));
+        }
+        b"renew" => {
+            let (resource_id, token_ids, periods, payer) = source.read().unwrap();
+            sink.write(renew(resource_id, token_ids, periods, payer));
+        }
         b"buyDTokenReward" => {
             let (resource_id, n, buyer_account, payer, unit_price) = source.read().unwrap();
             sink.write(buy_dtoken_reward(
@@ -583,6 +3310,18 @@ pub fn invoke() {
                 unit_price,
             ));
         }
+        b"bid" => {
+            let (resource_id, bidder, payer, price) = source.read().unwrap();
+            sink.write(bid(resource_id, bidder, payer, price));
+        }
+        b"getAuctionBid" => {
+            let resource_id = source.read().unwrap();
+            sink.write(get_auction_bid(resource_id));
+        }
+        b"settleAuction" => {
+            let resource_id = source.read().unwrap();
+            sink.write(settle_auction(resource_id));
+        }
         b"getTokenTemplates" => {
             let resource_id = source.read().unwrap();
             sink.write(get_token_template_ids(resource_id));
@@ -631,6 +3370,105 @@ mod utils {
     pub fn generate_seller_item_info_key(resource_id: &[u8]) -> Vec<u8> {
         [KEY_SELLER_ITEM_INFO, resource_id].concat()
     }
+    pub fn generate_auction_bid_key(resource_id: &[u8]) -> Vec<u8> {
+        [KEY_AUCTION_BID, resource_id].concat()
+    }
+    pub fn generate_sell_order_key(order_id: U128) -> Vec<u8> {
+        let mut sink = Sink::new(32);
+        sink.write(KEY_SELL_ORDER);
+        sink.write(order_id);
+        sink.bytes().to_vec()
+    }
+    pub fn generate_item_paused_key(resource_id: &[u8]) -> Vec<u8> {
+        [KEY_ITEM_PAUSED, resource_id].concat()
+    }
+    pub fn generate_access_list_key(resource_id: &[u8], buyer: &Address) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(resource_id);
+        sink.write(buyer);
+        [KEY_ACCESS_LIST, sink.bytes()].concat()
+    }
+    pub fn generate_buyer_purchase_key(resource_id: &[u8], buyer: &Address) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(resource_id);
+        sink.write(buyer);
+        [KEY_BUYER_PURCHASE, sink.bytes()].concat()
+    }
+    pub fn generate_signing_key_key(account: &Address) -> Vec<u8> {
+        [KEY_SIGNING_KEY, account.as_ref()].concat()
+    }
+    pub fn generate_order_key(order_id: &[u8]) -> Vec<u8> {
+        [KEY_ORDER, order_id].concat()
+    }
+    pub fn generate_buyer_order_count_key(buyer: &Address) -> Vec<u8> {
+        [KEY_BUYER_ORDER, buyer.as_ref()].concat()
+    }
+    pub fn generate_item_order_count_key(resource_id: &[u8]) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(resource_id);
+        [KEY_ITEM_ORDER, sink.bytes()].concat()
+    }
+    pub fn generate_order_index_key(count_key: &[u8], index: u64) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(index);
+        [count_key, sink.bytes()].concat()
+    }
+    pub fn generate_bundle_key(bundle_id: &[u8]) -> Vec<u8> {
+        [KEY_BUNDLE, bundle_id].concat()
+    }
+    pub fn generate_presale_key(resource_id: &[u8]) -> Vec<u8> {
+        [KEY_PRESALE, resource_id].concat()
+    }
+    pub fn generate_pledge_key(resource_id: &[u8], buyer: &Address) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(resource_id);
+        sink.write(buyer);
+        [KEY_PLEDGE, sink.bytes()].concat()
+    }
+    pub fn generate_intent_nonce_key(buyer: &Address, nonce: u64) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(buyer);
+        sink.write(nonce);
+        [KEY_INTENT_NONCE, sink.bytes()].concat()
+    }
+    pub fn generate_endorsement_nonce_key(nonce: u64) -> Vec<u8> {
+        let mut sink = Sink::new(16);
+        sink.write(nonce);
+        [KEY_ENDORSEMENT_NONCE, sink.bytes()].concat()
+    }
+    pub fn generate_listing_published_key(item_id: &[u8]) -> Vec<u8> {
+        [KEY_LISTING_PUBLISHED, item_id].concat()
+    }
+    pub fn generate_listing_nonce_key(seller: &Address, nonce: u64) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(seller);
+        sink.write(nonce);
+        [KEY_LISTING_NONCE, sink.bytes()].concat()
+    }
+    pub fn generate_manager_items_key(manager: &Address) -> Vec<u8> {
+        [KEY_MANAGER_ITEMS, manager.as_ref()].concat()
+    }
+    pub fn generate_template_items_key(token_template_id: &[u8]) -> Vec<u8> {
+        [KEY_TEMPLATE_ITEMS, token_template_id].concat()
+    }
+    pub fn generate_item_index_key(list: &[u8], index: u64) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(list);
+        sink.write(index);
+        [KEY_ITEM_INDEX, sink.bytes()].concat()
+    }
+    pub fn generate_item_position_key(list: &[u8], resource_id: &[u8]) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(list);
+        sink.write(resource_id);
+        [KEY_ITEM_POSITION, sink.bytes()].concat()
+    }
+    pub fn generate_coupon_redeemed_key(resource_id: &[u8], coupon_id: &[u8]) -> Vec<u8> {
+        let mut sink = Sink::new(64);
+        sink.write(resource_id);
+        sink.write(coupon_id);
+        [KEY_COUPON_REDEEMED, sink.bytes()].concat()
+    }
 }
 
 #[cfg(test)]
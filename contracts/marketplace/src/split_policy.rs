@@ -9,6 +9,27 @@ pub fn register(
         split_contract_addr,
         ("register", (resource_id, split_policy_param_bytes)),
     );
+    verify_register_result(res)
+}
+
+/// register with the off-chain signature of `receiver`, which should be verified by the caller
+pub fn register_signed(
+    split_contract_addr: &Address,
+    resource_id: &[u8],
+    split_policy_param_bytes: &[u8],
+    receiver: &Address,
+) -> bool {
+    let res = wasm::call_contract(
+        split_contract_addr,
+        (
+            "registerSigned",
+            (resource_id, split_policy_param_bytes, receiver),
+        ),
+    );
+    verify_register_result(res)
+}
+
+fn verify_register_result(res: Option<Vec<u8>>) -> bool {
     if let Some(r) = res {
         let mut source = Source::new(r.as_slice());
         let rr: bool = source.read().unwrap();
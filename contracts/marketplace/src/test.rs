@@ -1,5 +1,6 @@
 use super::*;
-use alloc::collections::btree_map::BTreeMap;
+use alloc::rc::Rc;
+use core::cell::RefCell;
 use hexutil::{read_hex, to_hex};
 use ostd::abi::{Decoder, Encoder};
 use ostd::mock::build_runtime;
@@ -7,6 +8,8 @@ use ostd::mock::contract_mock::Command;
 use ostd::prelude::String;
 use ostd::types::u128_from_neo_bytes;
 
+const ONG_CONTRACT_ADDRESS: Address = ostd::macros::base58!("AFmseVrdL9f9oyCzZefL9tG6UbvhfRZMHJ");
+
 #[test]
 fn test67() {
     #[derive(Encoder, Decoder, Debug)]
@@ -39,7 +42,7 @@ fn test67() {
 
     let bs = read_hex("0a6d6574686f644e616d650200000000000000000000000000000000000000001027000000000000000000000000000010270000000000000000000000000000102700000000000000000000000000000000000000000000000000000000000002036161610362626210270000000000000000000000000000").unwrap();
     let mut source = Source::new(bs.as_slice());
-    let method: &[u8] = source.read().unwrap();
+    let _method: &[u8] = source.read().unwrap();
     let (item, aaa): (DTokenItem, U128) = source.read().unwrap();
 
     println!("{}", item.expired_date);
@@ -61,9 +64,10 @@ fn test_token_template() {
     sink.write(tt.clone());
     let mut source = Source::new(sink.bytes());
     let tt2: TokenTemplate = source.read().unwrap();
+    assert_eq!(tt2.token_hash, tt.token_hash);
 
     let bs = read_hex("012c646174615f69645f63316235663139352d623431342d343535632d393464332d6466303565366563373635300120e2a740fa12bd94f0e242688e29f6d803f7671eb1f81bcfbdc1c3e213878e7dd4").unwrap_or_default();
-    let tt = TokenTemplate::from_bytes(bs.as_slice());
+    let _tt = TokenTemplate::from_bytes(bs.as_slice());
 }
 
 #[test]
@@ -71,9 +75,9 @@ fn test() {
     let data = read_hex("00010000000000017a0842016023031e8c24c7ea90cac9aa52f3b7da000100000000000001207d479be9ae1b65d3f0e98327c2eafc5f2e0e0693e15d175198735e0a8eec8f91000000").unwrap_or_default();
     let mut source = Source::new(&data);
 
-    let ddo = ResourceDDO::from_bytes(data.as_slice());
+    let _ddo = ResourceDDO::from_bytes(data.as_slice());
 
-    let method: &[u8] = source.read().unwrap();
+    let _method: &[u8] = source.read().unwrap();
     let (resource_id, ddo_bytes, item_bytes): (Vec<u8>, &[u8], &[u8]) = source.read().unwrap();
     println!("resource_id:{:?}", String::from_utf8(resource_id));
     let ddo = ResourceDDO::from_bytes(ddo_bytes);
@@ -95,6 +99,15 @@ fn dtoken_test() {
         stocks: 10000,
         sold: 1000,
         token_template_ids: vec![],
+        start_date: 0,
+        sale_mode: SaleMode::Fixed,
+        price_tiers: vec![],
+        dispute_window: 0,
+        royalty: 0,
+        buyer_access: BuyerAccess::Open,
+        credential: None,
+        buyer_limit: BuyerLimit::default(),
+        alt_fees: vec![],
     };
 
     let mut sink = Sink::new(16);
@@ -105,6 +118,716 @@ fn dtoken_test() {
     assert_eq!(item.sold, item2.sold);
 }
 
+// an item in the encoding of the first version, which only has the fields up to `token_template_ids`
+fn first_version_item(sink: &mut Sink) {
+    sink.write(Fee {
+        contract_addr: Address::repeat_byte(0),
+        contract_type: TokenType::ONG,
+        count: 10,
+    });
+    sink.write(10000u64);
+    sink.write(100u64);
+    sink.write(3u64);
+    sink.write(vec![b"template".to_vec()]);
+}
+
+#[test]
+fn decode_first_version_item_test() {
+    let manager = Address::repeat_byte(1);
+    let mut sink = Sink::new(64);
+    first_version_item(&mut sink);
+    let item = DTokenItem::from_bytes(sink.bytes());
+    assert_eq!((item.fee.count, item.expired_date), (10, 10000));
+    assert_eq!((item.stocks, item.sold), (100, 3));
+    assert_eq!(item.token_template_ids, vec![b"template".to_vec()]);
+    assert_eq!(item.start_date, 0);
+    assert!(matches!(item.sale_mode, SaleMode::Fixed));
+    assert!(item.price_tiers.is_empty());
+    assert_eq!((item.dispute_window, item.royalty), (0, 0));
+    assert!(matches!(item.buyer_access, BuyerAccess::Open));
+    assert!(item.credential.is_none());
+    assert_eq!(item.buyer_limit.max_per_buyer, 0);
+    assert!(item.alt_fees.is_empty());
+
+    // the stored item info of the first version
+    sink.write(test_ddo(&manager));
+    let info: SellerItemInfo = Source::new(sink.bytes()).read().unwrap();
+    assert_eq!((info.item.stocks, info.item.sold), (100, 3));
+    assert_eq!(info.resource_ddo.manager, manager);
+    assert!(matches!(info.item.sale_mode, SaleMode::Fixed));
+}
+
+#[test]
+fn seller_item_info_encoding_test() {
+    let mut item = test_item(10, 100);
+    item.start_date = 5;
+    item.royalty = 300;
+    item.buyer_access = BuyerAccess::Denylist;
+    let info = SellerItemInfo::new(item, test_ddo(&Address::repeat_byte(1)));
+    let mut sink = Sink::new(64);
+    sink.write(&info);
+    let info2: SellerItemInfo = Source::new(sink.bytes()).read().unwrap();
+    assert_eq!((info2.item.start_date, info2.item.royalty), (5, 300));
+    assert!(matches!(info2.item.buyer_access, BuyerAccess::Denylist));
+    assert_eq!(info2.resource_ddo.manager, Address::repeat_byte(1));
+}
+
+#[test]
+fn read_publish_args_test() {
+    let manager = Address::repeat_byte(1);
+    // the arguments of the first version
+    let mut sink = Sink::new(64);
+    sink.write(&b"item"[..]);
+    sink.write(test_ddo(&manager));
+    first_version_item(&mut sink);
+    sink.write(&b"split"[..]);
+    let (resource_id, ddo, item, split_policy_param) =
+        read_publish_args(&mut Source::new(sink.bytes()));
+    assert_eq!(resource_id, b"item");
+    assert_eq!(ddo.manager, manager);
+    assert_eq!((item.stocks, item.start_date), (100, 0));
+    assert_eq!(split_policy_param, b"split");
+
+    // the added item fields follow the split policy param
+    let mut item = test_item(10, 100);
+    item.start_date = 5;
+    item.sale_mode = SaleMode::Subscription(SubscriptionParam { period: 30 });
+    item.encode_ext(&mut sink);
+    let (_, _, item, split_policy_param) = read_publish_args(&mut Source::new(sink.bytes()));
+    assert_eq!((item.stocks, item.start_date), (100, 5));
+    assert!(matches!(item.sale_mode, SaleMode::Subscription(_)));
+    assert_eq!(split_policy_param, b"split");
+}
+
+// store an item info in the encoding of the first version
+fn store_first_version_item(resource_id: &[u8], manager: &Address) {
+    let mut sink = Sink::new(64);
+    first_version_item(&mut sink);
+    sink.write(test_ddo(manager));
+    runtime::storage_write(
+        utils::generate_seller_item_info_key(resource_id).as_slice(),
+        sink.bytes(),
+    );
+}
+
+#[test]
+fn buy_first_version_item_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    store_first_version_item(b"old", &manager);
+
+    handle.witness(&[buyer.clone()]);
+    assert_eq!(
+        buy_dtoken(b"old", 2, &buyer, &buyer),
+        vec![b"template".to_vec()]
+    );
+    let info = item_info(b"old");
+    assert_eq!(info.item.sold, 5);
+    assert!(matches!(info.item.sale_mode, SaleMode::Fixed));
+}
+
+#[test]
+#[should_panic]
+fn buy_before_start_date_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    let mut item = test_item(10, 100);
+    item.start_date = 100;
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        item,
+        b""
+    ));
+
+    handle.timestamp(99);
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+}
+
+#[test]
+fn resume_item_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    let mut item = test_item(10, 100);
+    item.start_date = 100;
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        item,
+        b""
+    ));
+    assert!(pause_item(b"item"));
+    assert!(is_item_paused(b"item"));
+    assert!(resume_item(b"item"));
+    assert!(!is_item_paused(b"item"));
+
+    handle.timestamp(100);
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+    assert_eq!(item_info(b"item").item.sold, 1);
+}
+
+#[test]
+#[should_panic]
+fn buy_paused_item_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    assert!(pause_item(b"item"));
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+}
+
+#[test]
+fn sale_mode_test() {
+    let mode = SaleMode::Auction(AuctionParam {
+        reserve_price: 100,
+        start_time: 1,
+        end_time: 10,
+        min_increment: 5,
+    });
+    let mut sink = Sink::new(16);
+    sink.write(&mode);
+    let mut source = Source::new(sink.bytes());
+    let mode2: SaleMode = source.read().unwrap();
+    match mode2 {
+        SaleMode::Auction(param) => {
+            assert_eq!(param.reserve_price, 100);
+            assert_eq!(param.min_increment, 5);
+        }
+        _ => panic!("decode sale mode failed"),
+    }
+}
+
+#[test]
+fn dutch_price_test() {
+    let param = DutchParam {
+        start_price: 1000,
+        floor_price: 200,
+        start_time: 100,
+        end_time: 200,
+    };
+    assert_eq!(param.price_at(50), 1000);
+    assert_eq!(param.price_at(100), 1000);
+    assert_eq!(param.price_at(150), 600);
+    assert_eq!(param.price_at(200), 200);
+    assert_eq!(param.price_at(300), 200);
+}
+
+#[test]
+fn buy_dutch_item_test() {
+    let calls = Calls::default();
+    let handle = dutch_item(calls.clone());
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    // the price has decayed from 1000 to 600
+    handle.timestamp(150);
+    assert_eq!(
+        buy_dtoken_with_max_price(b"item", 2, &buyer, &buyer, 600).len(),
+        1
+    );
+    // the floor price after the end time
+    handle.timestamp(300);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+
+    let paid: Vec<(Address, Vec<u8>, U128)> = calls_to(&calls, b"transferWithdraw");
+    assert_eq!((paid[0].2, paid[1].2), (1200, 200));
+    let orders = get_item_orders(b"item", 0, 10);
+    assert_eq!((orders[0].fee.count, orders[0].amount), (600, 1200));
+    assert_eq!((orders[1].fee.count, orders[1].amount), (200, 200));
+}
+
+#[test]
+#[should_panic]
+fn buy_dutch_item_above_max_price_test() {
+    let handle = dutch_item(Calls::default());
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    handle.timestamp(150);
+    buy_dtoken_with_max_price(b"item", 1, &buyer, &buyer, 599);
+}
+
+// publish a Dutch item whose unit price decays from 1000 at 100 to 200 at 200
+fn dutch_item(calls: Calls) -> ostd::mock::RuntimeHandle {
+    let mut item = test_item(0, 100);
+    item.sale_mode = SaleMode::Dutch(DutchParam {
+        start_price: 1000,
+        floor_price: 200,
+        start_time: 100,
+        end_time: 200,
+    });
+    publish_test_items(mock_contracts(calls), vec![(b"item".to_vec(), item)])
+}
+
+#[test]
+fn discount_test() {
+    assert_eq!(Discount::Percent(20).apply(1000), 800);
+    assert_eq!(Discount::Percent(100).apply(1000), 0);
+    assert_eq!(Discount::Fixed(300).apply(1000), 700);
+    assert_eq!(Discount::Fixed(3000).apply(1000), 0);
+}
+
+#[test]
+fn oracle_price_test() {
+    let handle = build_runtime();
+    let oracle = Address::repeat_byte(9);
+    let ong = Fee {
+        contract_addr: Address::repeat_byte(0),
+        contract_type: TokenType::ONG,
+        count: 0,
+    };
+    handle.on_contract_call(move |addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        if addr == &oracle {
+            mock_oracle_contract(data, 40000000, 100)
+        } else {
+            None
+        }
+    });
+    handle.witness(&[CONTRACT_COMMON.admin().clone()]);
+    assert!(set_oracle_contract(&oracle));
+    assert_eq!(oracle_unit_price(&ong, 25, 150, 60), 1000000000);
+}
+
+#[test]
+#[should_panic]
+fn oracle_stale_price_test() {
+    let handle = build_runtime();
+    let oracle = Address::repeat_byte(9);
+    let ong = Fee {
+        contract_addr: Address::repeat_byte(0),
+        contract_type: TokenType::ONG,
+        count: 0,
+    };
+    handle.on_contract_call(move |addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        if addr == &oracle {
+            mock_oracle_contract(data, 40000000, 100)
+        } else {
+            None
+        }
+    });
+    handle.witness(&[CONTRACT_COMMON.admin().clone()]);
+    assert!(set_oracle_contract(&oracle));
+    oracle_unit_price(&ong, 25, 200, 60);
+}
+
+#[test]
+#[should_panic]
+fn oracle_price_overflow_test() {
+    let handle = build_runtime();
+    let oracle = Address::repeat_byte(9);
+    let ong = Fee {
+        contract_addr: Address::repeat_byte(0),
+        contract_type: TokenType::ONG,
+        count: 0,
+    };
+    handle.on_contract_call(move |addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        if addr == &oracle {
+            mock_oracle_contract(data, u64::max_value() as U128, 100)
+        } else {
+            None
+        }
+    });
+    handle.witness(&[CONTRACT_COMMON.admin().clone()]);
+    assert!(set_oracle_contract(&oracle));
+    oracle_unit_price(&ong, 2, 150, 60);
+}
+
+#[test]
+fn buy_oracle_item_test() {
+    let handle = oracle_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    handle.timestamp(150);
+    buy_dtoken_with_oracle(b"item", 2, &buyer, &buyer, 0, 60, U128::max_value());
+    let orders = get_item_orders(b"item", 0, 10);
+    assert_eq!(orders[0].fee.count, 1000000000);
+    assert_eq!(orders[0].amount, 2000000000);
+}
+
+#[test]
+#[should_panic]
+fn buy_oracle_item_without_oracle_test() {
+    let handle = oracle_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    handle.timestamp(150);
+    buy_dtoken(b"item", 2, &buyer, &buyer);
+}
+
+// publish an item priced 25 in the reference unit, the oracle rate is 40000000 updated at 100
+fn oracle_item() -> ostd::mock::RuntimeHandle {
+    let oracle = Address::repeat_byte(9);
+    let contracts = mock_contracts(Calls::default());
+    let mut item = test_item(10, 100);
+    item.sale_mode = SaleMode::Oracle(OracleParam { ref_price: 25 });
+    let handle = publish_test_items(
+        move |addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+            if addr == &oracle {
+                mock_oracle_contract(data, 40000000, 100)
+            } else {
+                contracts(addr, data)
+            }
+        },
+        vec![(b"item".to_vec(), item)],
+    );
+    assert!(set_oracle_contract(&oracle));
+    handle
+}
+
+#[test]
+fn order_index_test() {
+    let _handle = build_runtime();
+    let buyer = Address::repeat_byte(1);
+    let fee = Fee {
+        contract_addr: Address::repeat_byte(0),
+        contract_type: TokenType::ONG,
+        count: 10,
+    };
+    for i in 0..3u8 {
+        let oi = OrderId {
+            item_id: vec![b'a' + i % 2],
+            tx_hash: ostd::types::H256::repeat_byte(i),
+        };
+        let order = new_order(&oi.to_bytes(), &buyer, &buyer, 2, &fee, 10, 20);
+        record_order(&order);
+    }
+    assert_eq!(get_buyer_order_count(&buyer), 3);
+    assert_eq!(get_item_order_count(b"a"), 2);
+    assert_eq!(get_item_order_count(b"b"), 1);
+    let orders = get_buyer_orders(&buyer, 1, 10);
+    assert_eq!(orders.len(), 2);
+    assert_eq!(orders[0].resource_id, b"b".to_vec());
+    assert_eq!(orders[1].amount, 20);
+    let orders = get_item_orders(b"a", 0, 1);
+    assert_eq!(orders.len(), 1);
+    assert!(get_item_orders(b"a", 5, 1).is_empty());
+    let oi = OrderId::from_bytes(orders[0].order_id.as_slice());
+    assert_eq!(oi.item_id, b"a".to_vec());
+}
+
+#[test]
+fn order_seq_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+
+    // the same item bought twice in one transaction
+    handle.witness(&[buyer.clone()]);
+    buy_dtokens(
+        vec![b"item".to_vec(), b"item".to_vec()],
+        vec![1, 2],
+        &buyer,
+        &buyer,
+    );
+    let orders = get_item_orders(b"item", 0, 10);
+    assert_eq!(orders.len(), 2);
+    assert_eq!(orders[1].n, 2);
+    assert_ne!(orders[0].order_id, orders[1].order_id);
+    let oi = OrderId::from_bytes(orders[1].order_id.as_slice());
+    assert_eq!(oi.item_id, b"item".to_vec());
+    assert_eq!(oi.to_bytes(), orders[0].order_id);
+    assert_eq!(get_buyer_order_count(&buyer), 2);
+}
+
+#[test]
+fn escrow_order_status_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let accountant = Address::repeat_byte(6);
+    let buyer = Address::repeat_byte(4);
+    let mut ddo = test_ddo(&manager);
+    ddo.accountant_contract_address = Some(accountant.clone());
+    let mut item = test_item(10, 100);
+    item.dispute_window = 100;
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(b"item", ddo, item, b""));
+
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+    let order = get_item_orders(b"item", 0, 1).pop().unwrap();
+    assert!(matches!(order.status, OrderStatus::Escrowed));
+    assert_eq!(order.accountant, Some(accountant.clone()));
+
+    handle.witness(&[accountant.clone()]);
+    assert!(update_order_status(order.order_id.as_slice(), true));
+    let order = get_item_orders(b"item", 0, 1).pop().unwrap();
+    assert!(matches!(order.status, OrderStatus::Refunded));
+}
+
+#[test]
+#[should_panic]
+fn update_order_status_by_others_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    let mut ddo = test_ddo(&manager);
+    ddo.accountant_contract_address = Some(Address::repeat_byte(6));
+    let mut item = test_item(10, 100);
+    item.dispute_window = 100;
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(b"item", ddo, item, b""));
+
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+    let order = get_item_orders(b"item", 0, 1).pop().unwrap();
+    update_order_status(order.order_id.as_slice(), true);
+}
+
+#[test]
+fn subscription_renew_test() {
+    let handle = build_runtime();
+    let calls = Calls::default();
+    handle.on_contract_call(mock_contracts(calls.clone()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    let mut item = test_item(10, 100);
+    item.sale_mode = SaleMode::Subscription(SubscriptionParam { period: 30 });
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(b"sub", test_ddo(&manager), item, b""));
+
+    handle.timestamp(100);
+    handle.witness(&[buyer.clone()]);
+    let token_ids = subscribe(b"sub", 2, &buyer, &buyer);
+    let generated: Vec<(Address, Vec<Vec<u8>>, U128, u64)> =
+        calls_to(&calls, b"generateDTokenWithValidityMulti");
+    assert_eq!(generated[0].3, 160);
+
+    assert!(renew(b"sub", token_ids, 3, &buyer));
+    let extended: Vec<(Vec<u8>, Vec<u8>, u64)> = calls_to(&calls, b"extendTokenValidity");
+    assert_eq!(extended.len(), 1);
+    assert_eq!(extended[0].2, 90);
+    let orders = get_item_orders(b"sub", 0, 10);
+    assert_eq!(orders.len(), 2);
+    assert_eq!(orders[1].n, 3);
+    assert_eq!(orders[1].amount, 30);
+}
+
+#[test]
+#[should_panic]
+fn renew_after_item_expired_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    let mut item = test_item(10, 100);
+    item.sale_mode = SaleMode::Subscription(SubscriptionParam { period: 30 });
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(b"sub", test_ddo(&manager), item, b""));
+
+    handle.timestamp(100);
+    handle.witness(&[buyer.clone()]);
+    let token_ids = subscribe(b"sub", 1, &buyer, &buyer);
+    handle.timestamp(20000);
+    renew(b"sub", token_ids, 1, &buyer);
+}
+
+#[test]
+#[should_panic]
+fn renew_paused_item_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    let mut item = test_item(10, 100);
+    item.sale_mode = SaleMode::Subscription(SubscriptionParam { period: 30 });
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(b"sub", test_ddo(&manager), item, b""));
+
+    handle.timestamp(100);
+    handle.witness(&[buyer.clone()]);
+    let token_ids = subscribe(b"sub", 1, &buyer, &buyer);
+    handle.witness(&[manager.clone()]);
+    assert!(pause_item(b"sub"));
+    handle.witness(&[buyer.clone()]);
+    renew(b"sub", token_ids, 1, &buyer);
+}
+
+#[test]
+fn item_index_test() {
+    let _handle = build_runtime();
+    let manager = Address::repeat_byte(1);
+    let templates = vec![b"t1".to_vec(), b"t2".to_vec()];
+    for id in [b"a", b"b", b"c"].iter() {
+        index_add(KEY_ALL_ITEMS, &id[..]);
+        index_item(&id[..], &manager, templates.as_slice(), true);
+    }
+    index_item(b"c", &manager, templates.as_slice(), true);
+    assert_eq!(get_item_count(), 3);
+    assert_eq!(get_manager_item_count(&manager), 3);
+    assert_eq!(get_template_item_count(b"t2"), 3);
+
+    index_remove(KEY_ALL_ITEMS, b"a");
+    index_item(b"a", &manager, templates.as_slice(), false);
+    index_remove(KEY_ALL_ITEMS, b"a");
+    assert_eq!(get_items(0, 10), vec![b"c".to_vec(), b"b".to_vec()]);
+    assert_eq!(get_items_by_manager(&manager, 1, 10), vec![b"b".to_vec()]);
+    assert_eq!(
+        get_items_by_token_template(b"t1", 0, 1),
+        vec![b"c".to_vec()]
+    );
+    assert!(get_items_by_token_template(b"t3", 0, 10).is_empty());
+}
+
+#[test]
+#[should_panic]
+fn auction_update_with_bid_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let bidder = Address::repeat_byte(5);
+    let mut item = test_item(0, 1);
+    item.sale_mode = SaleMode::Auction(AuctionParam {
+        reserve_price: 100,
+        start_time: 0,
+        end_time: 100,
+        min_increment: 10,
+    });
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"lot",
+        test_ddo(&manager),
+        item.clone(),
+        b""
+    ));
+    handle.witness(&[bidder.clone()]);
+    assert!(bid(b"lot", &bidder, &bidder, 100));
+
+    // the escrowed bid would be lost if the item could be turned into a fixed price item
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    item.sale_mode = SaleMode::Fixed;
+    update(b"lot", test_ddo(&manager), item, b"");
+}
+
+#[test]
+fn settle_auction_test() {
+    let calls = Calls::default();
+    let handle = auction_item(calls.clone());
+    let bidder = Address::repeat_byte(5);
+    let payer = Address::repeat_byte(6);
+    handle.timestamp(50);
+    handle.witness(&[bidder.clone(), payer.clone()]);
+    assert!(bid(b"lot", &bidder, &payer, 120));
+
+    // anyone can settle the auction, the bidder does not sign
+    handle.timestamp(100);
+    handle.witness(&[]);
+    assert_eq!(settle_auction(b"lot"), vec![b"template".to_vec()]);
+    let generated: Vec<(Address, Address, Vec<u8>, U128)> =
+        calls_to(&calls, b"generateDTokenForOther");
+    assert_eq!(generated.len(), 1);
+    assert_eq!(
+        (&generated[0].0, &generated[0].1, generated[0].3),
+        (&address(), &bidder, 1)
+    );
+    let paid: Vec<(Address, Vec<u8>, U128)> = calls_to(&calls, b"transferWithdraw");
+    assert_eq!(paid.len(), 1);
+    assert_eq!(paid[0].2, 120);
+    let orders = get_item_orders(b"lot", 0, 10);
+    assert_eq!(orders.len(), 1);
+    assert_eq!(orders[0].buyer, bidder);
+    assert_eq!(orders[0].payer, payer);
+    assert_eq!(orders[0].amount, 120);
+    assert_eq!(item_info(b"lot").item.sold, 1);
+    assert!(get_auction_bid(b"lot").is_empty());
+}
+
+#[test]
+fn outbid_refund_test() {
+    let calls = Calls::default();
+    let handle = auction_item(calls.clone());
+    let bidder = Address::repeat_byte(5);
+    let bidder2 = Address::repeat_byte(6);
+    handle.timestamp(50);
+    handle.witness(&[bidder.clone(), bidder2.clone()]);
+    assert!(bid(b"lot", &bidder, &bidder, 100));
+    assert!(bid(b"lot", &bidder2, &bidder2, 110));
+
+    // the first bid is refunded to its payer when it is outbid
+    assert_eq!(
+        ong_transfers(&calls),
+        vec![
+            (bidder.clone(), address(), 100),
+            (address(), bidder.clone(), 100),
+            (bidder2.clone(), address(), 110),
+        ]
+    );
+    let highest =
+        database::get::<_, Bid>(utils::generate_auction_bid_key(b"lot").as_slice()).unwrap();
+    assert_eq!(highest.bidder, bidder2);
+    assert_eq!(highest.price, 110);
+}
+
+#[test]
+#[should_panic]
+fn bid_below_min_increment_test() {
+    let handle = auction_item(Calls::default());
+    let bidder = Address::repeat_byte(5);
+    let bidder2 = Address::repeat_byte(6);
+    handle.timestamp(50);
+    handle.witness(&[bidder.clone(), bidder2.clone()]);
+    assert!(bid(b"lot", &bidder, &bidder, 100));
+    bid(b"lot", &bidder2, &bidder2, 109);
+}
+
+#[test]
+#[should_panic]
+fn settle_auction_before_end_test() {
+    let handle = auction_item(Calls::default());
+    let bidder = Address::repeat_byte(5);
+    handle.timestamp(50);
+    handle.witness(&[bidder.clone()]);
+    assert!(bid(b"lot", &bidder, &bidder, 100));
+    handle.timestamp(99);
+    settle_auction(b"lot");
+}
+
+// publish the lot of one dtoken auctioned from 0 to 100 in the marketplace Address::repeat_byte(8),
+// the reserve price is 100 and the minimum increment is 10,
+// dtokens can only be generated by the marketplace
+fn auction_item(calls: Calls) -> ostd::mock::RuntimeHandle {
+    let marketplace = Address::repeat_byte(8);
+    let mut item = test_item(0, 1);
+    item.sale_mode = SaleMode::Auction(AuctionParam {
+        reserve_price: 100,
+        start_time: 0,
+        end_time: 100,
+        min_increment: 10,
+    });
+    let handle = publish_test_items(
+        mock_signed_contracts(calls, vec![marketplace.clone()]),
+        vec![(b"lot".to_vec(), item)],
+    );
+    handle.address(&marketplace);
+    handle
+}
+
 #[test]
 fn test2() {
     let data = read_hex("0001000000012a6469643a6f6e743a41626b35725255794a53636e6d5045645264567934693769666955377967433853682096cae35ce8a9b0244178bf28e4966c2ce1b8385723a96a6b838858cdd6ca0a1e00675478ea7368fd9579c00a8a749d29c2b82f2aef10687474703a2f2f64656d6f2e7465737401000000012a6469643a6f6e743a41626b35725255794a53636e6d5045645264567934693769666955377967433853682096cae35ce8a9b0244178bf28e4966c2ce1b8385723a96a6b838858cdd6ca0a1e10687474703a2f2f64656d6f2e7465737400012fee6d8699c9b8f992a6bd54753cf84cb3aae8740000").unwrap_or_default();
@@ -118,17 +841,13 @@ fn test3() {
     let mut source = Source::new(&data);
     let mthod_name: &str = source.read().unwrap();
     println!("method_name:{}", mthod_name);
-    let (resource_id, ddo, item, sp): (&[u8], &[u8], &[u8], &[u8]) = source.read().unwrap();
-    //        println!("resource_id:{}", to_hex(resource_id));
-    //        let ddo = ResourceDDO::from_bytes(ddo);
-    //        println!("manager:{}", ddo.manager);
-    //        let item = DTokenItem::from_bytes(item);
-    //        println!("item:{}", item.stocks);
-
-    let build = build_runtime();
-    let addr = ostd::macros::base58!("Aejfo7ZX5PVpenRj23yChnyH64nf8T1zbu");
-    build.witness(&[addr]);
-    assert!(dtoken_seller_publish(resource_id, ddo, item, sp));
+    let (resource_id, ddo, _item, _sp): (&[u8], &[u8], &[u8], &[u8]) = source.read().unwrap();
+    println!("resource_id:{}", to_hex(resource_id));
+    assert_eq!(resource_id, b"5842878882420054248");
+    let ddo = ResourceDDO::from_bytes(ddo);
+    println!("manager:{}", ddo.manager);
+    assert!(ddo.dtoken_contract_address.is_empty());
+    assert!(ddo.split_policy_contract_address.is_none());
 }
 
 #[test]
@@ -138,19 +857,34 @@ fn test5() {
 
     let method_name: &str = source.read().unwrap();
     println!("method_name:{}", method_name);
+    // the arguments follow their count
+    let argc: u8 = source.read().unwrap();
+    assert_eq!(argc, 3);
     let (resource_id, n, buyer): (&[u8], U128, &Address) = source.read().unwrap();
+    assert_eq!(resource_id, b"reso_5");
 
     let build = build_runtime();
-    let addr = ostd::macros::base58!("AHhXa11suUgVLX1ZDFErqBd3gskKqLfa5N");
+    build.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    build.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        resource_id,
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
     build.witness(&[buyer.clone()]);
-    assert!(buy_dtoken(resource_id, n, buyer, buyer));
+    assert_eq!(
+        buy_dtoken(resource_id, n, buyer, buyer),
+        vec![b"template".to_vec()]
+    );
 }
 
 #[test]
 fn serialize() {
     let token_hash = read_hex("96cae35ce8a9b0244178bf28e4966c2ce1b8385723a96a6b838858cdd6ca0a1e")
         .unwrap_or_default();
-    let token_template = TokenTemplate::new(
+    let _token_template = TokenTemplate::new(
         vec![],
         vec![],
         Some(b"did:ont:Abk5rRUyJScnmPEdRdVy4i7ifiU7ygC8Sh".to_vec()),
@@ -160,8 +894,6 @@ fn serialize() {
 
     let manager = ostd::macros::base58!("ARCESVnP8Lbf6S7FuTei3smA35EQYog4LR");
 
-    let mp_contract_address = Address::repeat_byte(3);
-
     let dtoken_contract_hex =
         read_hex("2fee6d8699c9b8f992a6bd54753cf84cb3aae874").unwrap_or_default();
     let mut temp: [u8; 20] = [0; 20];
@@ -186,17 +918,8 @@ fn serialize() {
 #[test]
 fn publish() {
     let resource_id = b"resource_id";
-    let temp = vec![0u8; 36];
-    let token_template = TokenTemplate::new(
-        b"name".to_vec(),
-        b"symbol".to_vec(),
-        None,
-        vec![temp],
-        vec![],
-    );
     let manager = Address::repeat_byte(1);
     let dtoken_contract_address = Address::repeat_byte(2);
-    let mp_contract_address = Address::repeat_byte(3);
 
     let ddo = ResourceDDO {
         item_meta_hash: H256::repeat_byte(1),
@@ -218,33 +941,30 @@ fn publish() {
         contract_type: TokenType::ONG,
         count: 0,
     };
-    let mut templates = vec![b"template_id".to_vec()];
+    let templates = vec![b"template_id".to_vec()];
     let dtoken_item = DTokenItem {
         fee,
         expired_date: 1,
         stocks: 1000,
         sold: 1,
-        token_template_ids: templates,
+        token_template_ids: templates.clone(),
+        start_date: 0,
+        sale_mode: SaleMode::Fixed,
+        price_tiers: vec![],
+        dispute_window: 0,
+        royalty: 0,
+        buyer_access: BuyerAccess::Open,
+        credential: None,
+        buyer_limit: BuyerLimit::default(),
+        alt_fees: vec![],
     };
 
     let handle = build_runtime();
+    let calls = Calls::default();
+    handle.on_contract_call(mock_contracts(calls.clone()));
 
     let buyer = Address::repeat_byte(4);
-
-    let mut ong_balance_map = BTreeMap::<Address, U128>::new();
-    ong_balance_map.insert(buyer.clone(), 10000);
-    ong_balance_map.insert(manager.clone(), 10000);
     let buyer2 = Address::repeat_byte(5);
-    ong_balance_map.insert(buyer2.clone(), 10000);
-
-    let call_contract = move |_addr: &Address, _data: &[u8]| -> Option<Vec<u8>> {
-        if _addr == &dtoken_contract_address {
-            mock_dtoken_contract(_data, &mut ong_balance_map)
-        } else {
-            mock_mp_contract(_data, &mut ong_balance_map)
-        }
-    };
-    handle.on_contract_call(call_contract);
 
     handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
     assert!(set_dtoken_contract(&dtoken_contract_address));
@@ -252,94 +972,1684 @@ fn publish() {
     let split_param = b"test";
     assert!(dtoken_seller_publish(
         resource_id,
-        &ddo.to_bytes(),
-        &dtoken_item.to_bytes(),
+        ddo,
+        dtoken_item,
         split_param
     ));
 
     handle.witness(&[buyer.clone()]);
-    //    assert!(buy_dtoken(resource_id, 1, &buyer));
-    assert!(buy_dtokens(vec![resource_id], vec![1], &buyer, &buyer));
+    let token_ids = buy_dtokens(vec![resource_id.to_vec()], vec![1], &buyer, &buyer);
+    assert_eq!(token_ids, vec![templates.clone()]);
 
     handle.witness(&[buyer.clone(), buyer2.clone()]);
     assert!(buy_dtoken_from_reseller(resource_id, 1, &buyer2, &buyer));
-    let token_template_bytes = token_template.to_bytes();
-}
-
-fn mock_mp_contract(
-    _data: &[u8],
-    ong_balance_map: &mut BTreeMap<Address, U128>,
-) -> Option<Vec<u8>> {
-    let mut sink = Sink::new(12);
-    let mut source = Source::new(_data);
-    let command = Command::decode(&mut source).ok().unwrap();
-    match command {
-        Command::Transfer { from, to, value } => {
-            let mut from_ba = ong_balance_map.get(from).map(|val| val.clone()).unwrap();
-            let mut to_ba = ong_balance_map
-                .get(to)
-                .map(|val| val.clone())
-                .unwrap_or_default();
-            from_ba -= value;
-            to_ba += value;
-            ong_balance_map.insert(from.clone(), from_ba);
-            ong_balance_map.insert(to.clone(), to_ba);
-            sink.write(true);
-        }
-        Command::BalanceOf { addr } => {
-            let ba = ong_balance_map.get(addr).map(|val| val.clone()).unwrap();
-            sink.write(ba);
-        }
-    }
-    return Some(sink.bytes().to_vec());
+    let transferred: Vec<(Address, Address, Vec<u8>, U128)> =
+        contract_calls_to(&calls, &dtoken_contract_address, b"transferDToken");
+    assert_eq!(transferred.len(), 1);
+    assert_eq!((&transferred[0].0, &transferred[0].1), (&buyer, &buyer2));
+    assert_eq!(item_info(resource_id).item.sold, 2);
 }
 
-#[derive(Encoder, Decoder)]
-enum DtokenCommand {
-    GenerateDToken(GenerateDToken),
-    TransferDToken(TransferDToken),
-    UseToken(UseToken),
+// mock price oracle, every currency is worth `rate` per reference unit, updated at `updated_at`
+fn mock_oracle_contract(data: &[u8], rate: U128, updated_at: u64) -> Option<Vec<u8>> {
+    let mut source = Source::new(data);
+    let method: &[u8] = source.read().unwrap();
+    match method {
+        b"getPrice" => {
+            let (_contract_addr, _contract_type): (Address, TokenType) = source.read().unwrap();
+            let mut sink = Sink::new(12);
+            sink.write((rate, updated_at));
+            Some(sink.bytes().to_vec())
+        }
+        _ => None,
+    }
 }
 
-#[derive(Encoder, Decoder)]
-struct UseToken {
-    account: Address,
-    token_template: Vec<u8>,
-    n: U128,
+#[test]
+fn partial_update_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken(b"item", 2, &buyer, &buyer);
+
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    let tiers = vec![PriceTier {
+        min_n: 10,
+        count: 15,
+    }];
+    assert!(update_price(b"item", 20, tiers));
+    assert!(add_stocks(b"item", 5));
+    assert!(extend_expiry(b"item", 20000));
+    assert!(update_token_templates(b"item", vec![b"t2".to_vec()]));
+    let info = item_info(b"item");
+    assert_eq!(info.item.fee.count, 20);
+    assert_eq!(info.item.price_tiers.len(), 1);
+    assert_eq!(info.item.stocks, 105);
+    assert_eq!(info.item.sold, 2);
+    assert_eq!(info.item.expired_date, 20000);
+    assert_eq!(info.item.token_template_ids, vec![b"t2".to_vec()]);
 }
 
-#[derive(Encoder, Decoder)]
-struct GenerateDToken {
-    account: Address,
-    templates: Vec<u8>,
-    n: U128,
+#[test]
+fn update_test() {
+    let handle = build_runtime();
+    let calls = Calls::default();
+    handle.on_contract_call(mock_contracts(calls.clone()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b"split"
+    ));
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken(b"item", 3, &buyer, &buyer);
+
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(update(
+        b"item",
+        test_ddo(&manager),
+        test_item(20, 50),
+        b"split"
+    ));
+    let info = item_info(b"item");
+    assert_eq!((info.item.fee.count, info.item.stocks), (20, 50));
+    assert_eq!(info.item.sold, 3);
+    // the split policy is registered only when the item is published
+    let registered: Vec<(Vec<u8>, Vec<u8>)> = calls_to(&calls, b"register");
+    assert_eq!(registered.len(), 1);
 }
 
-#[derive(Encoder, Decoder)]
-struct TransferDToken {
-    from_account: Address,
-    to_account: Address,
-    templates: Vec<u8>,
-    n: U128,
+#[test]
+#[should_panic]
+fn update_split_policy_contract_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    let mut ddo = test_ddo(&manager);
+    ddo.split_policy_contract_address = Some(Address::repeat_byte(7));
+    update(b"item", ddo, test_item(10, 100), b"");
 }
 
-fn mock_dtoken_contract(
-    _data: &[u8],
-    ong_balance_map: &mut BTreeMap<Address, U128>,
-) -> Option<Vec<u8>> {
-    let mut sink = Sink::new(12);
-    let mut source = Source::new(_data);
-    let command = DtokenCommand::decode(&mut source).ok().unwrap();
-    match command {
-        DtokenCommand::GenerateDToken(generate) => {
-            sink.write(true);
-        }
-        DtokenCommand::TransferDToken(transfer) => {
-            sink.write(true);
+#[test]
+#[should_panic]
+fn update_price_overflow_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    update_price(b"item", u64::max_value() as U128 + 1, vec![]);
+}
+
+#[test]
+#[should_panic]
+fn add_stocks_overflow_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    add_stocks(b"item", u64::max_value() as U128);
+}
+
+#[test]
+#[should_panic]
+fn shorten_expiry_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    extend_expiry(b"item", 9999);
+}
+
+#[test]
+#[should_panic]
+fn update_price_by_others_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    handle.witness(&[Address::repeat_byte(4)]);
+    update_price(b"item", 20, vec![]);
+}
+
+#[test]
+fn buyer_limit_test() {
+    let handle = buyer_limit_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    handle.timestamp(100);
+    buy_dtoken(b"item", 3, &buyer, &buyer);
+    // a new window starts at 200
+    handle.timestamp(200);
+    buy_dtoken(b"item", 2, &buyer, &buyer);
+    let purchase = get_buyer_purchase(b"item", &buyer);
+    assert_eq!(purchase.total, 5);
+    assert_eq!(purchase.window_start, 200);
+    assert_eq!(purchase.window_count, 2);
+    // other buyers have their own limits
+    let buyer2 = Address::repeat_byte(5);
+    handle.witness(&[buyer2.clone()]);
+    buy_dtoken(b"item", 3, &buyer2, &buyer2);
+    assert_eq!(get_buyer_purchase(b"item", &buyer2).total, 3);
+}
+
+#[test]
+#[should_panic]
+fn window_limit_exceeded_test() {
+    let handle = buyer_limit_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    handle.timestamp(100);
+    buy_dtoken(b"item", 3, &buyer, &buyer);
+    handle.timestamp(199);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+}
+
+#[test]
+#[should_panic]
+fn buyer_limit_exceeded_test() {
+    let handle = buyer_limit_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    handle.timestamp(100);
+    buy_dtoken(b"item", 3, &buyer, &buyer);
+    handle.timestamp(200);
+    buy_dtoken(b"item", 2, &buyer, &buyer);
+    handle.timestamp(300);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+}
+
+// publish an item that allows 5 units per buyer and 3 units per buyer every 100 seconds
+fn buyer_limit_item() -> ostd::mock::RuntimeHandle {
+    let mut item = test_item(10, 100);
+    item.buyer_limit = BuyerLimit {
+        max_per_buyer: 5,
+        max_per_window: 3,
+        window: 100,
+    };
+    publish_test_items(
+        mock_contracts(Calls::default()),
+        vec![(b"item".to_vec(), item)],
+    )
+}
+
+#[test]
+fn allowlist_test() {
+    let handle = access_list_item(BuyerAccess::Allowlist);
+    let buyer = Address::repeat_byte(4);
+    let buyer2 = Address::repeat_byte(5);
+    handle.witness(&[buyer.clone(), buyer2.clone()]);
+    assert_eq!(buy_dtoken(b"item", 2, &buyer, &buyer).len(), 1);
+    assert!(buy_dtoken_from_reseller(b"item", 1, &buyer2, &buyer));
+    assert_eq!(item_info(b"item").item.sold, 2);
+}
+
+#[test]
+#[should_panic]
+fn allowlist_unlisted_buyer_test() {
+    let handle = access_list_item(BuyerAccess::Allowlist);
+    let buyer = Address::repeat_byte(6);
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+}
+
+#[test]
+#[should_panic]
+fn allowlist_unlisted_buyer_from_reseller_test() {
+    let handle = access_list_item(BuyerAccess::Allowlist);
+    let reseller = Address::repeat_byte(4);
+    let buyer = Address::repeat_byte(6);
+    handle.witness(&[reseller.clone(), buyer.clone()]);
+    assert_eq!(buy_dtoken(b"item", 1, &reseller, &reseller).len(), 1);
+    buy_dtoken_from_reseller(b"item", 1, &buyer, &reseller);
+}
+
+#[test]
+fn denylist_test() {
+    let handle = access_list_item(BuyerAccess::Denylist);
+    let buyer = Address::repeat_byte(6);
+    let buyer2 = Address::repeat_byte(7);
+    handle.witness(&[buyer.clone(), buyer2.clone()]);
+    assert_eq!(buy_dtoken(b"item", 2, &buyer, &buyer).len(), 1);
+    assert!(buy_dtoken_from_reseller(b"item", 1, &buyer2, &buyer));
+}
+
+#[test]
+#[should_panic]
+fn denylisted_buyer_test() {
+    let handle = access_list_item(BuyerAccess::Denylist);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+}
+
+#[test]
+#[should_panic]
+fn denylisted_buyer_from_reseller_test() {
+    let handle = access_list_item(BuyerAccess::Denylist);
+    let reseller = Address::repeat_byte(6);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[reseller.clone(), buyer.clone()]);
+    assert_eq!(buy_dtoken(b"item", 1, &reseller, &reseller).len(), 1);
+    buy_dtoken_from_reseller(b"item", 1, &buyer, &reseller);
+}
+
+#[test]
+fn remove_access_list_test() {
+    let _handle = access_list_item(BuyerAccess::Allowlist);
+    let buyer = Address::repeat_byte(4);
+    assert!(is_in_access_list(b"item", &buyer));
+    assert!(remove_access_list(b"item", vec![buyer.clone()]));
+    assert!(!is_in_access_list(b"item", &buyer));
+    assert!(is_in_access_list(b"item", &Address::repeat_byte(5)));
+}
+
+// publish an item priced 10 with `access`, the access list has Address::repeat_byte(4) and Address::repeat_byte(5)
+fn access_list_item(access: BuyerAccess) -> ostd::mock::RuntimeHandle {
+    let mut item = test_item(10, 100);
+    item.buyer_access = access;
+    let handle = publish_test_items(
+        mock_contracts(Calls::default()),
+        vec![(b"item".to_vec(), item)],
+    );
+    assert!(add_access_list(
+        b"item",
+        vec![Address::repeat_byte(4), Address::repeat_byte(5)]
+    ));
+    handle
+}
+
+#[test]
+fn buy_with_coupon_test() {
+    let handle = coupon_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    let coupon = test_coupon(None);
+    assert_eq!(
+        buy_dtoken_with_coupon(b"item", 2, &buyer, &buyer, coupon.clone(), b"sig").len(),
+        1
+    );
+    assert_eq!(get_coupon_redeemed(b"item", b"coupon"), 1);
+    let orders = get_item_orders(b"item", 0, 10);
+    assert_eq!(orders[0].fee.count, 8);
+    assert_eq!(orders[0].amount, 16);
+    // the coupon can be redeemed twice
+    buy_dtoken_with_coupon(b"item", 1, &buyer, &buyer, coupon, b"sig");
+    assert_eq!(get_coupon_redeemed(b"item", b"coupon"), 2);
+}
+
+#[test]
+#[should_panic]
+fn coupon_redemption_cap_test() {
+    let handle = coupon_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    for _ in 0..3 {
+        buy_dtoken_with_coupon(b"item", 1, &buyer, &buyer, test_coupon(None), b"sig");
+    }
+}
+
+#[test]
+fn coupon_for_buyer_test() {
+    let handle = coupon_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    let coupon = test_coupon(Some(buyer.clone()));
+    assert_eq!(
+        buy_dtoken_with_coupon(b"item", 1, &buyer, &buyer, coupon, b"sig").len(),
+        1
+    );
+}
+
+#[test]
+#[should_panic]
+fn coupon_for_other_buyer_test() {
+    let handle = coupon_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    let coupon = test_coupon(Some(Address::repeat_byte(5)));
+    buy_dtoken_with_coupon(b"item", 1, &buyer, &buyer, coupon, b"sig");
+}
+
+#[test]
+#[should_panic]
+fn expired_coupon_test() {
+    let handle = coupon_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    handle.timestamp(1001);
+    buy_dtoken_with_coupon(b"item", 1, &buyer, &buyer, test_coupon(None), b"sig");
+}
+
+// the signature of a coupon for another marketplace can not be used in this one
+#[test]
+#[should_panic]
+fn coupon_for_other_marketplace_test() {
+    let handle = coupon_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    let mut coupon = test_coupon(None);
+    coupon.marketplace = Address::repeat_byte(9);
+    buy_dtoken_with_coupon(b"item", 1, &buyer, &buyer, coupon, b"sig");
+}
+
+// the signature of a coupon for another item can not be used for this one
+#[test]
+#[should_panic]
+fn coupon_for_other_item_test() {
+    let handle = coupon_item();
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken_with_coupon(b"other", 1, &buyer, &buyer, test_coupon(None), b"sig");
+}
+
+// a coupon can not be verified if the manager has not set a signing key
+#[test]
+#[should_panic]
+fn coupon_without_signing_key_test() {
+    let handle = publish_test_items(
+        mock_contracts(Calls::default()),
+        vec![(b"item".to_vec(), test_item(10, 100))],
+    );
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken_with_coupon(b"item", 1, &buyer, &buyer, test_coupon(None), b"sig");
+}
+
+// publish an item priced 10, the manager Address::repeat_byte(1) has set a signing key
+fn coupon_item() -> ostd::mock::RuntimeHandle {
+    let handle = publish_test_items(
+        mock_contracts(Calls::default()),
+        vec![(b"item".to_vec(), test_item(10, 100))],
+    );
+    assert!(set_signing_key(&Address::repeat_byte(1), b"manager key"));
+    handle
+}
+
+// coupon of 20 percent off for `buyer`, which can be redeemed twice until 1000
+fn test_coupon(buyer: Option<Address>) -> Coupon {
+    Coupon {
+        marketplace: address(),
+        coupon_id: b"coupon".to_vec(),
+        item_id: b"item".to_vec(),
+        discount: Discount::Percent(20),
+        max_redemptions: 2,
+        expired_date: 1000,
+        buyer,
+    }
+}
+
+#[test]
+fn resale_royalty_test() {
+    let calls = Calls::default();
+    let mut item = test_item(10, 100);
+    item.royalty = 1000;
+    let handle = publish_test_items(
+        mock_contracts(calls.clone()),
+        vec![(b"item".to_vec(), item)],
+    );
+    let reseller = Address::repeat_byte(4);
+    let buyer = Address::repeat_byte(5);
+    handle.witness(&[reseller.clone(), buyer.clone()]);
+    assert!(buy_dtoken_from_reseller(b"item", 2, &buyer, &reseller));
+
+    // 10 percent of the price 2 * 10 is paid to the split policy of the item, the rest to the reseller
+    let royalty: Vec<(Address, Vec<u8>, U128)> = calls_to(&calls, b"transferWithdraw");
+    assert_eq!(royalty.len(), 1);
+    assert_eq!(
+        (&royalty[0].0, royalty[0].1.as_slice(), royalty[0].2),
+        (&buyer, &b"item"[..], 2)
+    );
+    assert_eq!(ong_transfers(&calls), vec![(buyer, reseller, 18)]);
+}
+
+#[test]
+fn buy_with_currency_test() {
+    let handle = build_runtime();
+    let calls = Calls::default();
+    handle.on_contract_call(mock_contracts(calls.clone()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    let oep4 = Address::repeat_byte(6);
+    let mut item = test_item(10, 100);
+    item.alt_fees = vec![Fee {
+        contract_addr: oep4.clone(),
+        contract_type: TokenType::OEP4,
+        count: 7,
+    }];
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        item,
+        b""
+    ));
+
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken_with_currency(b"item", 2, &buyer, &buyer, 1, 7);
+    // paid in a currency other than the registered one
+    let paid: Vec<(Address, Vec<u8>, U128, Fee)> = calls_to(&calls, b"transferWithdrawFee");
+    assert_eq!(paid.len(), 1);
+    assert_eq!(paid[0].2, 14);
+    assert_eq!(paid[0].3.contract_addr, oep4);
+    let orders = get_item_orders(b"item", 0, 10);
+    assert_eq!(orders[0].fee.contract_addr, oep4);
+    assert_eq!(orders[0].amount, 14);
+
+    // paid in the registered currency by the method every split policy contract supports
+    buy_dtoken_with_currency(b"item", 1, &buyer, &buyer, 0, 10);
+    let paid: Vec<(Address, Vec<u8>, U128)> = calls_to(&calls, b"transferWithdraw");
+    assert_eq!(paid.len(), 1);
+    assert_eq!(paid[0].2, 10);
+    assert_eq!(item_info(b"item").item.sold, 3);
+}
+
+#[test]
+#[should_panic]
+fn buy_with_unaccepted_currency_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"item",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken_with_currency(b"item", 1, &buyer, &buyer, 1, U128::max_value());
+}
+
+#[test]
+fn checkout_test() {
+    let handle = build_runtime();
+    let calls = Calls::default();
+    handle.on_contract_call(mock_contracts(calls.clone()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"a",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    assert!(dtoken_seller_publish(
+        b"b",
+        test_ddo(&manager),
+        test_item(5, 100),
+        b""
+    ));
+
+    handle.witness(&[buyer.clone()]);
+    let receipts = checkout(
+        vec![b"a".to_vec(), b"b".to_vec()],
+        vec![2, 3],
+        &buyer,
+        &buyer,
+    );
+    assert_eq!(receipts.len(), 2);
+    assert_eq!(receipts[0].amount, 20);
+    assert_eq!(receipts[1].amount, 15);
+    // the same item is bought again
+    let receipts = checkout(vec![b"a".to_vec()], vec![1], &buyer, &buyer);
+    assert_eq!(receipts[0].amount, 10);
+
+    // one payment to the default split policy contract per checkout
+    let paid: Vec<(Address, Vec<Vec<u8>>, Vec<U128>, Fee)> =
+        calls_to(&calls, b"transferWithdrawMulti");
+    assert_eq!(paid.len(), 2);
+    assert_eq!(paid[0].1, vec![b"a".to_vec(), b"b".to_vec()]);
+    assert_eq!(paid[0].2, vec![20, 15]);
+    assert_eq!(paid[1].1, vec![b"a".to_vec()]);
+    assert_eq!(paid[1].2, vec![10]);
+    assert_eq!(item_info(b"a").item.sold, 3);
+    assert_eq!(item_info(b"b").item.sold, 3);
+    assert_eq!(get_buyer_order_count(&buyer), 3);
+}
+
+#[test]
+fn checkout_without_multi_transfer_test() {
+    let calls = Calls::default();
+    let contracts = mock_contracts(calls.clone());
+    let handle = publish_test_items(
+        move |addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+            let mut source = Source::new(data);
+            match source.read::<&[u8]>() {
+                // a split policy contract deployed before `transferWithdrawMulti` was added
+                Ok(b"transferWithdrawMulti") => None,
+                _ => contracts(addr, data),
+            }
+        },
+        vec![
+            (b"a".to_vec(), test_item(10, 100)),
+            (b"b".to_vec(), test_item(5, 100)),
+        ],
+    );
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    let receipts = checkout(
+        vec![b"a".to_vec(), b"b".to_vec()],
+        vec![2, 3],
+        &buyer,
+        &buyer,
+    );
+    assert_eq!(receipts.len(), 2);
+    // the items are paid one by one
+    let paid: Vec<(Address, Vec<u8>, U128)> = calls_to(&calls, b"transferWithdraw");
+    assert_eq!(paid.len(), 2);
+    assert_eq!((paid[0].1.as_slice(), paid[0].2), (&b"a"[..], 20));
+    assert_eq!((paid[1].1.as_slice(), paid[1].2), (&b"b"[..], 15));
+    assert_eq!(item_info(b"a").item.sold, 2);
+    assert_eq!(item_info(b"b").item.sold, 3);
+}
+
+#[test]
+#[should_panic]
+fn checkout_duplicate_item_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(dtoken_seller_publish(
+        b"a",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    handle.witness(&[buyer.clone()]);
+    checkout(
+        vec![b"a".to_vec(), b"a".to_vec()],
+        vec![1, 1],
+        &buyer,
+        &buyer,
+    );
+}
+
+#[test]
+fn buy_bundle_test() {
+    let handle = bundle_items(&[10, 30]);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    let token_ids = buy_bundle(b"bundle", 2, &buyer, &buyer);
+    assert_eq!(token_ids.len(), 2);
+    // 2 bundles cost 40, split by the unit prices of the components
+    assert_eq!(get_item_orders(b"0", 0, 10)[0].amount, 10);
+    assert_eq!(get_item_orders(b"1", 0, 10)[0].amount, 30);
+    assert_eq!(get_item_orders(b"1", 0, 10)[0].fee.count, 15);
+    assert_eq!(item_info(b"0").item.sold, 2);
+    assert_eq!(item_info(b"1").item.sold, 2);
+}
+
+#[test]
+fn buy_bundle_rounding_test() {
+    let handle = bundle_items(&[10, 10, 10]);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    buy_bundle(b"bundle", 1, &buyer, &buyer);
+    // the last component gets the remainder
+    assert_eq!(get_item_orders(b"0", 0, 10)[0].amount, 6);
+    assert_eq!(get_item_orders(b"1", 0, 10)[0].amount, 6);
+    assert_eq!(get_item_orders(b"2", 0, 10)[0].amount, 8);
+    for id in [b"0", b"1", b"2"].iter() {
+        assert_eq!(item_info(&id[..]).item.sold, 1);
+    }
+}
+
+#[test]
+#[should_panic]
+fn buy_expired_bundle_test() {
+    let handle = bundle_items(&[10, 30]);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    handle.timestamp(5001);
+    buy_bundle(b"bundle", 1, &buyer, &buyer);
+}
+
+// publish one item for every unit price, named by its index, and a bundle of them priced 20
+// on sale until 5000
+fn bundle_items(prices: &[u64]) -> ostd::mock::RuntimeHandle {
+    let items = prices
+        .iter()
+        .enumerate()
+        .map(|(i, &price)| (i.to_string().into_bytes(), test_item(price, 100)))
+        .collect::<Vec<_>>();
+    let resource_ids = items.iter().map(|(id, _)| id.clone()).collect();
+    let handle = publish_test_items(mock_contracts(Calls::default()), items);
+    let fee = test_item(20, 0).fee;
+    assert!(publish_bundle(b"bundle", resource_ids, fee, 5000));
+    handle
+}
+
+#[test]
+fn finalize_presale_test() {
+    let calls = Calls::default();
+    let handle = presale_item(calls.clone(), 3);
+    let buyer = Address::repeat_byte(4);
+    let buyer2 = Address::repeat_byte(5);
+    handle.timestamp(100);
+    handle.witness(&[buyer.clone(), buyer2.clone()]);
+    assert!(commit_presale(b"item", 2, &buyer, &buyer));
+    assert!(commit_presale(b"item", 2, &buyer2, &buyer2));
+
+    handle.timestamp(1000);
+    assert!(finalize_presale(b"item"));
+    // the escrowed 4 dtokens are paid at once
+    let paid: Vec<(Address, Vec<u8>, U128)> = calls_to(&calls, b"transferWithdraw");
+    assert_eq!(paid.len(), 1);
+    assert_eq!(paid[0].2, 40);
+
+    // both buyers claim in one transaction which they do not sign
+    handle.witness(&[]);
+    assert_eq!(claim_presale(b"item", &buyer).len(), 1);
+    assert_eq!(claim_presale(b"item", &buyer2).len(), 1);
+    let generated: Vec<(Address, Address, Vec<u8>, U128)> =
+        calls_to(&calls, b"generateDTokenForOther");
+    assert_eq!(generated.len(), 2);
+    assert_eq!((&generated[0].0, &generated[0].1), (&address(), &buyer));
+    assert_eq!((&generated[1].0, &generated[1].1), (&address(), &buyer2));
+    assert_eq!(generated[1].3, 2);
+    let orders = get_item_orders(b"item", 0, 10);
+    assert_eq!(orders.len(), 2);
+    assert_ne!(orders[0].order_id, orders[1].order_id);
+    assert_eq!(orders[1].buyer, buyer2);
+    assert_eq!(orders[1].amount, 20);
+    assert_eq!(orders[1].fee.count, 10);
+    assert!(get_pledge(b"item", &buyer).is_empty());
+}
+
+#[test]
+fn refund_presale_test() {
+    let calls = Calls::default();
+    let handle = presale_item(calls.clone(), 5);
+    let buyer = Address::repeat_byte(4);
+    handle.timestamp(100);
+    handle.witness(&[buyer.clone()]);
+    assert!(commit_presale(b"item", 1, &buyer, &buyer));
+    assert!(commit_presale(b"item", 1, &buyer, &buyer));
+
+    handle.timestamp(1000);
+    assert!(claim_presale(b"item", &buyer).is_empty());
+    assert!(get_pledge(b"item", &buyer).is_empty());
+    assert_eq!(get_item_order_count(b"item"), 0);
+    assert!(calls_to::<(Address, Vec<u8>, U128)>(&calls, b"transferWithdraw").is_empty());
+}
+
+#[test]
+#[should_panic]
+fn finalize_presale_below_min_sold_test() {
+    let handle = presale_item(Calls::default(), 5);
+    let buyer = Address::repeat_byte(4);
+    handle.timestamp(100);
+    handle.witness(&[buyer.clone()]);
+    assert!(commit_presale(b"item", 2, &buyer, &buyer));
+    handle.timestamp(1000);
+    finalize_presale(b"item");
+}
+
+#[test]
+#[should_panic]
+fn claim_presale_before_deadline_test() {
+    let handle = presale_item(Calls::default(), 1);
+    let buyer = Address::repeat_byte(4);
+    handle.timestamp(100);
+    handle.witness(&[buyer.clone()]);
+    assert!(commit_presale(b"item", 2, &buyer, &buyer));
+    claim_presale(b"item", &buyer);
+}
+
+// publish a pre-sale item priced 10 which needs `min_sold` dtokens committed before 1000
+// in the marketplace Address::repeat_byte(8), dtokens can only be generated by the marketplace
+fn presale_item(calls: Calls, min_sold: u64) -> ostd::mock::RuntimeHandle {
+    let marketplace = Address::repeat_byte(8);
+    let mut item = test_item(10, 100);
+    item.sale_mode = SaleMode::Presale(PresaleParam {
+        min_sold,
+        deadline: 1000,
+    });
+    let handle = publish_test_items(
+        mock_signed_contracts(calls, vec![marketplace.clone()]),
+        vec![(b"item".to_vec(), item)],
+    );
+    handle.address(&marketplace);
+    handle
+}
+
+#[test]
+fn endorsed_publish_test() {
+    let handle = endorsement_runtime();
+    let manager = Address::repeat_byte(1);
+    let item = test_item(10, 100);
+    let endorsement = endorse(b"item", &test_ddo(&manager), &item, b"split", 1);
+    handle.witness(&[manager.clone()]);
+    assert!(!is_endorsement_nonce_used(1));
+    assert!(dtoken_seller_publish_endorsed(
+        b"item",
+        test_ddo(&manager),
+        item,
+        b"split",
+        endorsement,
+        b"sig"
+    ));
+    assert!(is_endorsement_nonce_used(1));
+    assert_eq!(item_info(b"item").item.fee.count, 10);
+}
+
+#[test]
+#[should_panic]
+fn endorsement_nonce_reused_test() {
+    let handle = endorsement_runtime();
+    let manager = Address::repeat_byte(1);
+    let item = test_item(10, 100);
+    handle.witness(&[manager.clone()]);
+    let endorsement = endorse(b"a", &test_ddo(&manager), &item, b"", 1);
+    assert!(dtoken_seller_publish_endorsed(
+        b"a",
+        test_ddo(&manager),
+        item.clone(),
+        b"",
+        endorsement,
+        b"sig"
+    ));
+    let endorsement = endorse(b"b", &test_ddo(&manager), &item, b"", 1);
+    dtoken_seller_publish_endorsed(b"b", test_ddo(&manager), item, b"", endorsement, b"sig");
+}
+
+#[test]
+#[should_panic]
+fn endorsement_split_param_changed_test() {
+    let handle = endorsement_runtime();
+    let manager = Address::repeat_byte(1);
+    let item = test_item(10, 100);
+    handle.witness(&[manager.clone()]);
+    let endorsement = endorse(b"item", &test_ddo(&manager), &item, b"split", 1);
+    dtoken_seller_publish_endorsed(
+        b"item",
+        test_ddo(&manager),
+        item,
+        b"other split",
+        endorsement,
+        b"sig",
+    );
+}
+
+#[test]
+#[should_panic]
+fn endorsement_expired_test() {
+    let handle = endorsement_runtime();
+    let manager = Address::repeat_byte(1);
+    let item = test_item(10, 100);
+    handle.witness(&[manager.clone()]);
+    let endorsement = endorse(b"item", &test_ddo(&manager), &item, b"", 1);
+    handle.timestamp(1001);
+    dtoken_seller_publish_endorsed(b"item", test_ddo(&manager), item, b"", endorsement, b"sig");
+}
+
+// runtime in which the marketplace admin has set a signing key
+fn endorsement_runtime() -> ostd::mock::RuntimeHandle {
+    let handle = publish_test_items(mock_contracts(Calls::default()), vec![]);
+    assert!(set_signing_key(CONTRACT_COMMON.admin(), b"admin key"));
+    handle
+}
+
+// endorsement of an item valid until 1000
+fn endorse(
+    resource_id: &[u8],
+    resource_ddo: &ResourceDDO,
+    item: &DTokenItem,
+    split_policy_param: &[u8],
+    nonce: u64,
+) -> Endorsement {
+    Endorsement {
+        marketplace: address(),
+        item_id: resource_id.to_vec(),
+        item_hash: endorsement_item_hash(resource_ddo, item, split_policy_param),
+        expired_date: 1000,
+        nonce,
+    }
+}
+
+#[test]
+fn buy_from_listing_test() {
+    let calls = Calls::default();
+    let handle = listing_runtime(calls.clone());
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    let token_ids = buy_dtoken_from_listing(test_listing(), b"sig", b"sig", 2, &buyer, &buyer, 10);
+    assert_eq!(token_ids.len(), 1);
+    // the split policy is registered on behalf of the seller
+    let registered: Vec<(Vec<u8>, Vec<u8>, Address)> = calls_to(&calls, b"registerSigned");
+    assert_eq!(registered.len(), 1);
+    assert_eq!(registered[0].2, Address::repeat_byte(1));
+    assert_eq!(item_info(b"item").item.sold, 2);
+    // the authorization is checked without the signature of the token template creator
+    assert_eq!(
+        calls_to::<(Vec<u8>,)>(&calls, b"getAuthorizedAddr").len(),
+        1
+    );
+    assert!(calls_to::<Vec<Vec<u8>>>(&calls, b"verifyCreatorSigMulti").is_empty());
+    // later purchases use the published item
+    buy_dtoken_from_listing(test_listing(), b"sig", b"sig", 1, &buyer, &buyer, 10);
+    assert_eq!(item_info(b"item").item.sold, 3);
+}
+
+#[test]
+#[should_panic]
+fn buy_from_changed_listing_test() {
+    let handle = listing_runtime(Calls::default());
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken_from_listing(test_listing(), b"sig", b"sig", 1, &buyer, &buyer, 10);
+    // the published item is bought from another listing with the same item id
+    let mut listing = test_listing();
+    listing.item = test_item(1, 100);
+    buy_dtoken_from_listing(listing, b"sig", b"sig", 1, &buyer, &buyer, 10);
+}
+
+#[test]
+#[should_panic]
+fn buy_unlisted_item_from_listing_test() {
+    let handle = publish_test_items(
+        mock_contracts(Calls::default()),
+        vec![(b"item".to_vec(), test_item(10, 100))],
+    );
+    assert!(set_signing_key(&Address::repeat_byte(1), b"seller key"));
+    assert!(set_signing_key(CONTRACT_COMMON.admin(), b"admin key"));
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken_from_listing(test_listing(), b"sig", b"sig", 1, &buyer, &buyer, 10);
+}
+
+#[test]
+#[should_panic]
+fn buy_from_unauthorized_listing_test() {
+    let contracts = mock_contracts(Calls::default());
+    let handle = publish_test_items(
+        move |addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+            let mut source = Source::new(data);
+            match source.read::<&[u8]>() {
+                // the token templates have not authorized any address
+                Ok(b"getAuthorizedAddr") => {
+                    let mut sink = Sink::new(16);
+                    sink.write(Vec::<Address>::new());
+                    Some(sink.bytes().to_vec())
+                }
+                _ => contracts(addr, data),
+            }
+        },
+        vec![],
+    );
+    assert!(set_signing_key(&Address::repeat_byte(1), b"seller key"));
+    assert!(set_signing_key(CONTRACT_COMMON.admin(), b"admin key"));
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken_from_listing(test_listing(), b"sig", b"sig", 1, &buyer, &buyer, 10);
+}
+
+#[test]
+#[should_panic]
+fn buy_from_revoked_listing_test() {
+    let handle = listing_runtime(Calls::default());
+    let seller = Address::repeat_byte(1);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[seller.clone()]);
+    assert!(revoke_listing(&seller, 7));
+    assert!(is_listing_revoked(&seller, 7));
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken_from_listing(test_listing(), b"sig", b"sig", 1, &buyer, &buyer, 10);
+}
+
+#[test]
+#[should_panic]
+fn buy_from_expired_listing_test() {
+    let handle = listing_runtime(Calls::default());
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    handle.timestamp(1001);
+    buy_dtoken_from_listing(test_listing(), b"sig", b"sig", 1, &buyer, &buyer, 10);
+}
+
+#[test]
+#[should_panic]
+fn buy_from_listing_above_max_price_test() {
+    let handle = listing_runtime(Calls::default());
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    buy_dtoken_from_listing(test_listing(), b"sig", b"sig", 1, &buyer, &buyer, 9);
+}
+
+// runtime in which the seller Address::repeat_byte(1) and the marketplace admin have set signing keys
+fn listing_runtime(calls: Calls) -> ostd::mock::RuntimeHandle {
+    let handle = publish_test_items(mock_contracts(calls), vec![]);
+    let seller = Address::repeat_byte(1);
+    assert!(set_signing_key(&seller, b"seller key"));
+    assert!(set_signing_key(CONTRACT_COMMON.admin(), b"admin key"));
+    handle
+}
+
+// listing of an item priced 10 by the seller Address::repeat_byte(1), valid until 1000 with nonce 7
+fn test_listing() -> Listing {
+    Listing {
+        marketplace: address(),
+        item_id: b"item".to_vec(),
+        resource_ddo: test_ddo(&Address::repeat_byte(1)),
+        item: test_item(10, 100),
+        split_policy_param: b"split".to_vec(),
+        expired_date: 1000,
+        nonce: 7,
+    }
+}
+
+#[test]
+fn publish_multi_test() {
+    let handle = build_runtime();
+    let calls = Calls::default();
+    handle.on_contract_call(mock_contracts(calls.clone()));
+    let manager = Address::repeat_byte(1);
+    let manager2 = Address::repeat_byte(2);
+    let item = test_item(10, 100);
+    let mut item2 = test_item(20, 100);
+    item2.token_template_ids = vec![b"template".to_vec(), b"template2".to_vec()];
+    handle.witness(&[
+        manager.clone(),
+        manager2.clone(),
+        CONTRACT_COMMON.admin().clone(),
+    ]);
+    assert!(dtoken_seller_publish_multi(vec![
+        (b"a".to_vec(), test_ddo(&manager), item, vec![]),
+        (b"b".to_vec(), test_ddo(&manager2), item2, vec![]),
+    ]));
+    assert_eq!(item_info(b"a").item.fee.count, 10);
+    assert_eq!(item_info(b"b").resource_ddo.manager, manager2);
+    // the shared token template is verified and authorized once
+    let verified: Vec<Vec<Vec<u8>>> = calls_to(&calls, b"verifyCreatorSigMulti");
+    assert_eq!(verified.len(), 1);
+    assert_eq!(
+        verified[0],
+        vec![b"template".to_vec(), b"template2".to_vec()]
+    );
+    let authorized: Vec<(Vec<Vec<u8>>, Vec<Address>)> =
+        calls_to(&calls, b"authorizeTokenTemplateMulti");
+    assert_eq!(authorized.len(), 1);
+    assert_eq!(authorized[0].1, vec![address()]);
+}
+
+#[test]
+#[should_panic]
+fn publish_multi_duplicate_id_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    dtoken_seller_publish_multi(vec![
+        (
+            b"a".to_vec(),
+            test_ddo(&manager),
+            test_item(10, 100),
+            vec![],
+        ),
+        (
+            b"a".to_vec(),
+            test_ddo(&manager),
+            test_item(20, 100),
+            vec![],
+        ),
+    ]);
+}
+
+#[test]
+#[should_panic]
+fn publish_multi_with_invalid_item_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let mut item2 = test_item(20, 100);
+    item2.start_date = 20000;
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    // the transaction fails as a whole, so the valid item is not published either
+    dtoken_seller_publish_multi(vec![
+        (
+            b"a".to_vec(),
+            test_ddo(&manager),
+            test_item(10, 100),
+            vec![],
+        ),
+        (b"b".to_vec(), test_ddo(&manager), item2, vec![]),
+    ]);
+}
+
+#[test]
+fn verify_auth_multi_test() {
+    let handle = build_runtime();
+    let calls = Calls::default();
+    handle.on_contract_call(mock_contracts(calls.clone()));
+    let dtoken = Address::repeat_byte(7);
+    let dtokens = vec![dtoken.clone()];
+    let templates = vec![b"t1".to_vec(), b"t2".to_vec()];
+    let default_templates = vec![b"t3".to_vec(), b"t4".to_vec()];
+    verify_auth_multi(&[
+        (dtokens.as_slice(), templates.as_slice()),
+        (&[], default_templates.as_slice()),
+    ]);
+    let verified = calls.borrow();
+    // like `verify_auth`, only the templates paired with a dtoken contract are handled
+    let to_dtoken: Vec<&(Address, Vec<u8>)> = verified
+        .iter()
+        .filter(|(addr, _)| addr == &dtoken)
+        .collect();
+    assert_eq!(to_dtoken.len(), 2);
+    let ids: Vec<Vec<Vec<u8>>> = calls_to(&calls, b"verifyCreatorSigMulti");
+    assert_eq!(ids, vec![vec![b"t1".to_vec()], default_templates.clone()]);
+    let to_default = verified
+        .iter()
+        .filter(|(addr, _)| addr == &get_dtoken_contract())
+        .count();
+    assert_eq!(to_default, 2);
+}
+
+#[test]
+fn item_index_lifecycle_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    let manager2 = Address::repeat_byte(2);
+    handle.witness(&[
+        manager.clone(),
+        manager2.clone(),
+        CONTRACT_COMMON.admin().clone(),
+    ]);
+    assert!(dtoken_seller_publish(
+        b"a",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    assert!(dtoken_seller_publish(
+        b"b",
+        test_ddo(&manager),
+        test_item(10, 100),
+        b""
+    ));
+    assert_eq!(get_item_count(), 2);
+    assert_eq!(get_manager_item_count(&manager), 2);
+    assert_eq!(get_template_item_count(b"template"), 2);
+
+    // the item moves to the index of the new manager
+    assert!(update(b"a", test_ddo(&manager2), test_item(20, 100), b""));
+    assert_eq!(get_item_count(), 2);
+    assert_eq!(get_items_by_manager(&manager, 0, 10), vec![b"b".to_vec()]);
+    assert_eq!(get_items_by_manager(&manager2, 0, 10), vec![b"a".to_vec()]);
+
+    assert!(update_token_templates(b"b", vec![b"t2".to_vec()]));
+    assert_eq!(
+        get_items_by_token_template(b"template", 0, 10),
+        vec![b"a".to_vec()]
+    );
+    assert_eq!(
+        get_items_by_token_template(b"t2", 0, 10),
+        vec![b"b".to_vec()]
+    );
+
+    assert!(delete(b"a"));
+    assert_eq!(get_items(0, 10), vec![b"b".to_vec()]);
+    assert_eq!(get_manager_item_count(&manager2), 0);
+    assert_eq!(get_template_item_count(b"template"), 0);
+}
+
+#[test]
+fn index_items_test() {
+    let handle = build_runtime();
+    let manager = Address::repeat_byte(1);
+    // items published by the first version, before the indexes were introduced
+    for id in [b"a", b"b"].iter() {
+        store_first_version_item(&id[..], &manager);
+    }
+    handle.witness(&[CONTRACT_COMMON.admin().clone()]);
+    assert!(index_items(vec![b"a".to_vec()]));
+    assert!(index_items(vec![b"a".to_vec(), b"b".to_vec()]));
+    assert_eq!(get_item_count(), 2);
+    assert_eq!(get_manager_item_count(&manager), 2);
+    assert_eq!(
+        get_items_by_token_template(b"template", 0, 10),
+        vec![b"a".to_vec(), b"b".to_vec()]
+    );
+}
+
+#[test]
+fn update_unindexed_item_test() {
+    let handle = build_runtime();
+    handle.on_contract_call(mock_contracts(Calls::default()));
+    let manager = Address::repeat_byte(1);
+    store_first_version_item(b"a", &manager);
+    store_first_version_item(b"b", &manager);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    assert!(update(b"a", test_ddo(&manager), test_item(20, 100), b""));
+    assert!(update_token_templates(b"b", vec![b"t2".to_vec()]));
+    assert_eq!(get_items(0, 10), vec![b"a".to_vec(), b"b".to_vec()]);
+    assert_eq!(get_manager_item_count(&manager), 2);
+    assert_eq!(item_info(b"a").item.sold, 3);
+}
+
+#[test]
+#[should_panic]
+fn index_items_by_others_test() {
+    let handle = build_runtime();
+    let manager = Address::repeat_byte(1);
+    database::put(
+        utils::generate_seller_item_info_key(b"a"),
+        SellerItemInfo::new(test_item(10, 100), test_ddo(&manager)),
+    );
+    handle.witness(&[manager.clone()]);
+    index_items(vec![b"a".to_vec()]);
+}
+
+#[test]
+fn fill_sell_order_test() {
+    let calls = Calls::default();
+    let handle = sell_order_runtime(calls.clone());
+    let seller = Address::repeat_byte(4);
+    let buyer = Address::repeat_byte(5);
+    handle.witness(&[buyer.clone()]);
+    assert!(fill_sell_order(0, 2, &buyer, &buyer));
+    let order: SellOrder = Source::new(get_sell_order(0).as_slice()).read().unwrap();
+    assert_eq!(order.amount, 3);
+    // the reseller is paid 2 * 3 directly
+    let paid: Vec<(Address, Address, U128)> =
+        contract_calls_to(&calls, &Address::repeat_byte(6), b"transfer");
+    assert_eq!(paid.len(), 1);
+    assert_eq!((&paid[0].0, &paid[0].1, paid[0].2), (&buyer, &seller, 6));
+
+    assert!(fill_sell_order(0, 3, &buyer, &buyer));
+    assert!(get_sell_order(0).is_empty());
+    // the escrowed dtokens are released by the marketplace
+    let released: Vec<(Address, Address, Vec<u8>, U128)> =
+        contract_calls_to(&calls, &get_dtoken_contract(), b"transfer");
+    assert_eq!(released.len(), 2);
+    assert!(released
+        .iter()
+        .all(|(from, to, _, _)| from == &address() && to == &buyer));
+    assert_eq!((released[0].3, released[1].3), (2, 3));
+}
+
+#[test]
+fn cancel_sell_order_test() {
+    let calls = Calls::default();
+    let handle = sell_order_runtime(calls.clone());
+    let seller = Address::repeat_byte(4);
+    let buyer = Address::repeat_byte(5);
+    handle.witness(&[buyer.clone()]);
+    assert!(fill_sell_order(0, 2, &buyer, &buyer));
+    handle.witness(&[seller.clone()]);
+    assert!(cancel_sell_order(0));
+    assert!(get_sell_order(0).is_empty());
+    // the remaining 3 dtokens are returned to the reseller
+    let transferred: Vec<(Address, Address, Vec<u8>, U128)> =
+        contract_calls_to(&calls, &get_dtoken_contract(), b"transfer");
+    assert_eq!(transferred.len(), 2);
+    assert_eq!(transferred[1].1, seller);
+    assert_eq!(transferred[1].3, 3);
+}
+
+#[test]
+#[should_panic]
+fn cancel_sell_order_by_others_test() {
+    let handle = sell_order_runtime(Calls::default());
+    let buyer = Address::repeat_byte(5);
+    handle.witness(&[buyer.clone()]);
+    cancel_sell_order(0);
+}
+
+#[test]
+#[should_panic]
+fn fill_sell_order_over_amount_test() {
+    let handle = sell_order_runtime(Calls::default());
+    let buyer = Address::repeat_byte(5);
+    handle.witness(&[buyer.clone()]);
+    fill_sell_order(0, 6, &buyer, &buyer);
+}
+
+#[test]
+#[should_panic]
+fn fill_expired_sell_order_test() {
+    let handle = sell_order_runtime(Calls::default());
+    let buyer = Address::repeat_byte(5);
+    handle.timestamp(1001);
+    handle.witness(&[buyer.clone()]);
+    fill_sell_order(0, 1, &buyer, &buyer);
+}
+
+#[test]
+#[should_panic]
+fn post_expired_sell_order_test() {
+    let handle = sell_order_runtime(Calls::default());
+    let seller = Address::repeat_byte(4);
+    let price = Fee {
+        contract_addr: Address::repeat_byte(6),
+        contract_type: TokenType::OEP4,
+        count: 3,
+    };
+    handle.timestamp(1000);
+    handle.witness(&[seller.clone()]);
+    post_sell_order(&seller, b"item", b"template", 1, price, 1000);
+}
+
+// publish an item and post the sell order 0 of 5 dtokens priced 3 in an OEP4 token, which expires at 1000
+fn sell_order_runtime(calls: Calls) -> ostd::mock::RuntimeHandle {
+    let handle = publish_test_items(
+        mock_contracts(calls),
+        vec![(b"item".to_vec(), test_item(10, 100))],
+    );
+    let seller = Address::repeat_byte(4);
+    handle.witness(&[seller.clone()]);
+    let price = Fee {
+        contract_addr: Address::repeat_byte(6),
+        contract_type: TokenType::OEP4,
+        count: 3,
+    };
+    assert_eq!(
+        post_sell_order(&seller, b"item", b"template", 5, price, 1000),
+        0
+    );
+    handle
+}
+
+#[test]
+fn buy_with_credential_test() {
+    let _handle = credential_item();
+    let buyer = Address::repeat_byte(4);
+    let token_ids = buy_dtoken_with_credential(b"item", 2, &buyer, &buyer, b"did:ont:holder", 1);
+    assert_eq!(token_ids, vec![b"template".to_vec()]);
+    assert_eq!(item_info(b"item").item.sold, 2);
+}
+
+#[test]
+#[should_panic]
+fn buy_with_credential_not_issued_test() {
+    let _handle = credential_item();
+    let buyer = Address::repeat_byte(4);
+    buy_dtoken_with_credential(b"item", 1, &buyer, &buyer, b"did:ont:other", 1);
+}
+
+#[test]
+#[should_panic]
+fn buy_without_credential_test() {
+    let _handle = credential_item();
+    let buyer = Address::repeat_byte(4);
+    buy_dtoken(b"item", 1, &buyer, &buyer);
+}
+
+// the resale paths do not pass the buyer's ONT ID
+#[test]
+#[should_panic]
+fn resell_credential_item_test() {
+    let handle = credential_item();
+    let reseller = Address::repeat_byte(4);
+    let buyer = Address::repeat_byte(5);
+    handle.witness(&[reseller.clone(), buyer.clone()]);
+    buy_dtoken_from_reseller(b"item", 1, &buyer, &reseller);
+}
+
+// publish an item which requires the claim b"kyc" from b"did:ont:issuer",
+// only b"did:ont:holder" holds the claim, every ONT ID signature is valid.
+// the native ONT ID contract is told apart by the method names in the call data
+fn credential_item() -> ostd::mock::RuntimeHandle {
+    let contracts = mock_contracts(Calls::default());
+    let mut ont_id_contract = [0u8; 20];
+    ont_id_contract[19] = 3;
+    let ont_id_contract = Address::new(ont_id_contract);
+    let mut item = test_item(10, 100);
+    item.credential = Some(CredentialRequirement {
+        issuer: b"did:ont:issuer".to_vec(),
+        claim: b"kyc".to_vec(),
+    });
+    let handle = publish_test_items(
+        move |addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+            if addr != &ont_id_contract {
+                return contracts(addr, data);
+            }
+            let contains = |pat: &[u8]| data.windows(pat.len()).any(|w| w == pat);
+            let mut sink = Sink::new(16);
+            if contains(b"verifySignature") {
+                sink.write(true);
+            } else if contains(b"getAttributeByKey") && contains(b"did:ont:holder") {
+                sink.write_bytes(b"kyc");
+            }
+            Some(sink.bytes().to_vec())
+        },
+        vec![(b"item".to_vec(), item)],
+    );
+    handle.witness(&[Address::repeat_byte(4)]);
+    handle
+}
+
+#[test]
+fn buy_by_intent_test() {
+    let calls = Calls::default();
+    let handle = intent_item(calls.clone());
+    let buyer = Address::repeat_byte(4);
+    let relayer = Address::repeat_byte(5);
+    handle.witness(&[relayer.clone()]);
+    assert!(!is_intent_nonce_used(&buyer, 1));
+    let token_ids = buy_dtoken_by_intent(test_intent(1), b"sig", &relayer);
+    assert_eq!(token_ids, vec![b"template".to_vec()]);
+    assert!(is_intent_nonce_used(&buyer, 1));
+    // the buyer does not sign, the dtokens are generated by the marketplace
+    let generated: Vec<(Address, Address, Vec<u8>, U128)> =
+        calls_to(&calls, b"generateDTokenForOther");
+    assert_eq!(generated.len(), 1);
+    assert_eq!((&generated[0].0, &generated[0].1), (&address(), &buyer));
+    assert_eq!(
+        (generated[0].2.as_slice(), generated[0].3),
+        (&b"template"[..], 2)
+    );
+    // the dtokens are generated for the buyer, the fee is paid by the relayer
+    let orders = get_item_orders(b"item", 0, 10);
+    assert_eq!(orders[0].buyer, buyer);
+    assert_eq!(orders[0].amount, 20);
+
+    // another nonce of the same buyer
+    buy_dtoken_by_intent(test_intent(2), b"sig", &relayer);
+    assert_eq!(item_info(b"item").item.sold, 4);
+}
+
+#[test]
+#[should_panic]
+fn intent_nonce_reused_test() {
+    let handle = intent_item(Calls::default());
+    let relayer = Address::repeat_byte(5);
+    handle.witness(&[relayer.clone()]);
+    buy_dtoken_by_intent(test_intent(1), b"sig", &relayer);
+    buy_dtoken_by_intent(test_intent(1), b"sig", &relayer);
+}
+
+#[test]
+#[should_panic]
+fn intent_after_deadline_test() {
+    let handle = intent_item(Calls::default());
+    let relayer = Address::repeat_byte(5);
+    handle.witness(&[relayer.clone()]);
+    handle.timestamp(1001);
+    buy_dtoken_by_intent(test_intent(1), b"sig", &relayer);
+}
+
+#[test]
+#[should_panic]
+fn intent_above_max_price_test() {
+    let handle = intent_item(Calls::default());
+    let relayer = Address::repeat_byte(5);
+    handle.witness(&[relayer.clone()]);
+    let mut intent = test_intent(1);
+    intent.max_unit_price = 9;
+    buy_dtoken_by_intent(intent, b"sig", &relayer);
+}
+
+// publish an item priced 10 in the marketplace Address::repeat_byte(8), the buyer has set a signing key,
+// dtokens can only be generated by the relayer Address::repeat_byte(5) and the marketplace
+fn intent_item(calls: Calls) -> ostd::mock::RuntimeHandle {
+    let marketplace = Address::repeat_byte(8);
+    let handle = publish_test_items(
+        mock_signed_contracts(calls, vec![marketplace.clone(), Address::repeat_byte(5)]),
+        vec![(b"item".to_vec(), test_item(10, 100))],
+    );
+    handle.address(&marketplace);
+    let buyer = Address::repeat_byte(4);
+    handle.witness(&[buyer.clone()]);
+    assert!(set_signing_key(&buyer, b"buyer key"));
+    handle
+}
+
+// intent of buying 2 dtokens at most 10 each, valid until 1000
+fn test_intent(nonce: u64) -> PurchaseIntent {
+    PurchaseIntent {
+        marketplace: address(),
+        item_id: b"item".to_vec(),
+        n: 2,
+        max_unit_price: 10,
+        buyer: Address::repeat_byte(4),
+        nonce,
+        deadline: 1000,
+    }
+}
+
+// contract calls made by the marketplace, recorded by `mock_contracts`
+type Calls = Rc<RefCell<Vec<(Address, Vec<u8>)>>>;
+
+// mock of the dtoken, split policy, accountant and token contracts, every call succeeds and is recorded,
+// the dtokens generated by the dtoken contracts have the same ids as their token templates
+fn mock_contracts(calls: Calls) -> impl Fn(&Address, &[u8]) -> Option<Vec<u8>> {
+    move |addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        calls.borrow_mut().push((addr.clone(), data.to_vec()));
+        let mut source = Source::new(data);
+        let mut sink = Sink::new(16);
+        match source.read::<&[u8]>() {
+            Ok(b"generateDTokenMulti") | Ok(b"generateDTokenWithValidityMulti") => {
+                let (_account, token_template_ids): (Address, Vec<Vec<u8>>) =
+                    source.read().unwrap();
+                sink.write(token_template_ids);
+            }
+            Ok(b"generateDToken") | Ok(b"generateDTokenWithValidity") => {
+                let (_account, token_template_id): (Address, Vec<u8>) = source.read().unwrap();
+                sink.write(token_template_id);
+            }
+            Ok(b"generateDTokenForOther") => {
+                let (_account, _to, token_template_id): (Address, Address, Vec<u8>) =
+                    source.read().unwrap();
+                sink.write(token_template_id);
+            }
+            // the token templates have authorized the calling marketplace
+            Ok(b"getAuthorizedAddr") => sink.write(vec![address()]),
+            Ok(b"getTemplateIdByTokenId") => {
+                let (token_id,): (Vec<u8>,) = source.read().unwrap();
+                sink.write(token_id);
+            }
+            // the split policy contract rejects a key registered before
+            Ok(b"register") | Ok(b"registerSigned") => {
+                let key: Vec<u8> = source.read().unwrap();
+                let registered = calls
+                    .borrow()
+                    .iter()
+                    .filter(|(a, d)| {
+                        let mut source = Source::new(d.as_slice());
+                        let is_register = match source.read::<&[u8]>() {
+                            Ok(m) => m == b"register" || m == b"registerSigned",
+                            _ => false,
+                        };
+                        a == addr
+                            && is_register
+                            && source.read::<Vec<u8>>().ok() == Some(key.clone())
+                    })
+                    .count();
+                if registered > 1 {
+                    return None;
+                }
+                sink.write(true);
+            }
+            // split policies are registered in ONG
+            Ok(b"getRegisterParam") => {
+                let receivers: Vec<(Address, u32, bool)> = vec![];
+                sink.write((receivers, TokenType::ONG, None::<Address>));
+            }
+            _ => sink.write(true),
         }
-        DtokenCommand::UseToken(useToken) => {
-            sink.write(true);
+        Some(sink.bytes().to_vec())
+    }
+}
+
+// same as `mock_contracts`, but the dtoken contracts reject generating dtokens by an account which is not one of
+// `signers`, the calling marketplace is a signer of its calls
+fn mock_signed_contracts(
+    calls: Calls,
+    signers: Vec<Address>,
+) -> impl Fn(&Address, &[u8]) -> Option<Vec<u8>> {
+    let contracts = mock_contracts(calls);
+    move |addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        let mut source = Source::new(data);
+        let method: &[u8] = source.read().unwrap();
+        if method.starts_with(b"generateDToken") {
+            let account: Address = source.read().unwrap();
+            if !signers.contains(&account) {
+                return None;
+            }
         }
+        contracts(addr, data)
+    }
+}
+
+// arguments of the recorded calls to `method`, in the order they were made
+fn calls_to<T: for<'a> Decoder<'a>>(calls: &Calls, method: &[u8]) -> Vec<T> {
+    calls
+        .borrow()
+        .iter()
+        .filter_map(|(_, data)| {
+            let mut source = Source::new(data.as_slice());
+            match source.read::<&[u8]>() {
+                Ok(m) if m == method => Some(source.read().unwrap()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// arguments of the recorded calls to `method` of the contract `addr`
+fn contract_calls_to<T: for<'a> Decoder<'a>>(
+    calls: &Calls,
+    addr: &Address,
+    method: &[u8],
+) -> Vec<T> {
+    let contract_calls: Calls = Rc::new(RefCell::new(
+        calls
+            .borrow()
+            .iter()
+            .filter(|(a, _)| a == addr)
+            .cloned()
+            .collect(),
+    ));
+    calls_to(&contract_calls, method)
+}
+
+// native ONG transfers of the recorded calls as `(from, to, value)`, in the order they were made
+fn ong_transfers(calls: &Calls) -> Vec<(Address, Address, U128)> {
+    calls
+        .borrow()
+        .iter()
+        .filter(|(addr, _)| addr == &ONG_CONTRACT_ADDRESS)
+        .filter_map(|(_, data)| {
+            let mut source = Source::new(data.as_slice());
+            match Command::decode(&mut source) {
+                Ok(Command::Transfer { from, to, value }) => {
+                    Some((from.clone(), to.clone(), value))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// runtime whose contract calls are answered by `contracts`, in which `items` are published as
+// `(resource_id, item)` by the manager Address::repeat_byte(1) with `test_ddo`,
+// the manager and the marketplace admin are the signers when it returns
+fn publish_test_items<F>(
+    contracts: F,
+    items: Vec<(Vec<u8>, DTokenItem)>,
+) -> ostd::mock::RuntimeHandle
+where
+    F: Fn(&Address, &[u8]) -> Option<Vec<u8>> + 'static,
+{
+    let handle = build_runtime();
+    handle.on_contract_call(contracts);
+    let manager = Address::repeat_byte(1);
+    handle.witness(&[manager.clone(), CONTRACT_COMMON.admin().clone()]);
+    for (resource_id, item) in items {
+        assert!(dtoken_seller_publish(
+            resource_id.as_slice(),
+            test_ddo(&manager),
+            item,
+            b""
+        ));
     }
-    return Some(sink.bytes().to_vec());
+    handle
+}
+
+// resource ddo using the default dtoken and split policy contracts
+fn test_ddo(manager: &Address) -> ResourceDDO {
+    ResourceDDO {
+        item_meta_hash: H256::repeat_byte(1),
+        manager: manager.clone(),
+        dtoken_contract_address: vec![],
+        accountant_contract_address: None,
+        split_policy_contract_address: None,
+    }
+}
+
+// fixed price item paid in ONG, on sale until 10000
+fn test_item(count: u64, stocks: u64) -> DTokenItem {
+    DTokenItem {
+        fee: Fee {
+            contract_addr: Address::repeat_byte(0),
+            contract_type: TokenType::ONG,
+            count,
+        },
+        expired_date: 10000,
+        stocks,
+        sold: 0,
+        token_template_ids: vec![b"template".to_vec()],
+        start_date: 0,
+        sale_mode: SaleMode::Fixed,
+        price_tiers: vec![],
+        dispute_window: 0,
+        royalty: 0,
+        buyer_access: BuyerAccess::Open,
+        credential: None,
+        buyer_limit: BuyerLimit::default(),
+        alt_fees: vec![],
+    }
+}
+
+fn item_info(resource_id: &[u8]) -> SellerItemInfo {
+    database::get::<_, SellerItemInfo>(utils::generate_seller_item_info_key(resource_id)).unwrap()
 }
@@ -0,0 +1,207 @@
+#![cfg_attr(not(feature = "mock"), no_std)]
+#![feature(proc_macro_hygiene)]
+extern crate ontio_std as ostd;
+use ostd::abi::{Decoder, Encoder, EventBuilder, Sink, Source};
+use ostd::database;
+use ostd::prelude::*;
+use ostd::runtime::{check_witness, input, ret, storage_read};
+extern crate common;
+use common::CONTRACT_COMMON;
+
+#[cfg(test)]
+mod test;
+
+const KEY_MARKETPLACE: &[u8] = b"01";
+const KEY_MARKETPLACE_INDEX: &[u8] = b"02";
+const KEY_MARKETPLACE_POSITION: &[u8] = b"03";
+const KEY_MARKETPLACE_COUNT: &[u8] = b"04";
+
+const MAX_FEE_RATE: u16 = 10000;
+const MAX_PAGE_SIZE: u64 = 100;
+
+#[derive(Encoder, Decoder, Clone)]
+pub struct MarketplaceInfo {
+    pub marketplace: Address, // marketplace contract address
+    pub admin: Address,       // marketplace operator
+    pub endpoint: Vec<u8>,
+    pub fee_rate: u16, // fee charged by the marketplace, in the range of 0 to 10000
+}
+
+/// register a marketplace, need the signatures of the marketplace contract and the admin
+///
+/// the marketplace contract registers itself by calling this method, so that nobody can register
+/// a marketplace he does not operate
+///
+/// `marketplace` is the marketplace contract address
+///
+/// `admin` is the operator of the marketplace
+///
+/// `endpoint` is where the browser can query the items of the marketplace
+///
+/// `fee_rate` is the fee charged by the marketplace, in the range of 0 to 10000
+pub fn register(marketplace: &Address, admin: &Address, endpoint: &[u8], fee_rate: u16) -> bool {
+    assert!(check_witness(marketplace) && check_witness(admin));
+    assert!(fee_rate <= MAX_FEE_RATE);
+    let key = generate_marketplace_key(marketplace);
+    assert!(storage_read(key.as_slice()).is_none());
+    let info = MarketplaceInfo {
+        marketplace: marketplace.clone(),
+        admin: admin.clone(),
+        endpoint: endpoint.to_vec(),
+        fee_rate,
+    };
+    database::put(key, &info);
+    let count = get_marketplace_count();
+    database::put(generate_marketplace_index_key(count), marketplace);
+    database::put(generate_marketplace_position_key(marketplace), count);
+    database::put(KEY_MARKETPLACE_COUNT, count + 1);
+    EventBuilder::new()
+        .string("register")
+        .address(marketplace)
+        .address(admin)
+        .bytearray(endpoint)
+        .number(fee_rate as U128)
+        .notify();
+    true
+}
+
+/// update the registration of a marketplace, need the signature of the current admin
+///
+/// `new_admin` also needs to sign if the admin is changed
+pub fn update(marketplace: &Address, new_admin: &Address, endpoint: &[u8], fee_rate: u16) -> bool {
+    let key = generate_marketplace_key(marketplace);
+    let mut info = database::get::<_, MarketplaceInfo>(key.as_slice()).expect("not registered");
+    assert!(check_witness(&info.admin) && check_witness(new_admin));
+    assert!(fee_rate <= MAX_FEE_RATE);
+    info.admin = new_admin.clone();
+    info.endpoint = endpoint.to_vec();
+    info.fee_rate = fee_rate;
+    database::put(key, &info);
+    EventBuilder::new()
+        .string("update")
+        .address(marketplace)
+        .address(new_admin)
+        .bytearray(endpoint)
+        .number(fee_rate as U128)
+        .notify();
+    true
+}
+
+/// deregister a marketplace, need the signature of the marketplace admin or the registry admin
+pub fn deregister(marketplace: &Address) -> bool {
+    let key = generate_marketplace_key(marketplace);
+    let info = database::get::<_, MarketplaceInfo>(key.as_slice()).expect("not registered");
+    assert!(check_witness(&info.admin) || check_witness(CONTRACT_COMMON.admin()));
+    database::delete(key);
+    // move the last marketplace to the position of the deregistered one
+    let position_key = generate_marketplace_position_key(marketplace);
+    let position = database::get::<_, u64>(position_key.as_slice()).unwrap();
+    let last = get_marketplace_count() - 1;
+    if position != last {
+        let last_addr = database::get::<_, Address>(generate_marketplace_index_key(last)).unwrap();
+        database::put(generate_marketplace_index_key(position), &last_addr);
+        database::put(generate_marketplace_position_key(&last_addr), position);
+    }
+    database::delete(generate_marketplace_index_key(last));
+    database::delete(position_key);
+    database::put(KEY_MARKETPLACE_COUNT, last);
+    EventBuilder::new()
+        .string("deregister")
+        .address(marketplace)
+        .notify();
+    true
+}
+
+/// query whether the marketplace is registered
+pub fn is_registered(marketplace: &Address) -> bool {
+    storage_read(generate_marketplace_key(marketplace).as_slice()).is_some()
+}
+
+/// query the registration of a marketplace, return empty bytes if it is not registered
+pub fn get_marketplace(marketplace: &Address) -> Vec<u8> {
+    storage_read(generate_marketplace_key(marketplace).as_slice()).unwrap_or_default()
+}
+
+/// the number of registered marketplaces
+pub fn get_marketplace_count() -> u64 {
+    database::get::<_, u64>(KEY_MARKETPLACE_COUNT).unwrap_or(0)
+}
+
+/// enumerate the registered marketplaces
+///
+/// `start` is the index of the first marketplace, `limit` is the max number of marketplaces returned, at most 100.
+/// the order changes when a marketplace is deregistered
+pub fn get_marketplaces(start: u64, limit: u64) -> Vec<MarketplaceInfo> {
+    let end = get_marketplace_count().min(start.saturating_add(limit.min(MAX_PAGE_SIZE)));
+    let mut res = vec![];
+    for i in start..end {
+        let marketplace = database::get::<_, Address>(generate_marketplace_index_key(i)).unwrap();
+        res.push(
+            database::get::<_, MarketplaceInfo>(generate_marketplace_key(&marketplace)).unwrap(),
+        );
+    }
+    res
+}
+
+fn generate_marketplace_key(marketplace: &Address) -> Vec<u8> {
+    [KEY_MARKETPLACE, marketplace.as_ref()].concat()
+}
+
+fn generate_marketplace_index_key(index: u64) -> Vec<u8> {
+    let mut sink = Sink::new(16);
+    sink.write(index);
+    [KEY_MARKETPLACE_INDEX, sink.bytes()].concat()
+}
+
+fn generate_marketplace_position_key(marketplace: &Address) -> Vec<u8> {
+    [KEY_MARKETPLACE_POSITION, marketplace.as_ref()].concat()
+}
+
+#[no_mangle]
+pub fn invoke() {
+    let input = input();
+    let mut source = Source::new(&input);
+    let action: &[u8] = source.read().unwrap();
+    let mut sink = Sink::new(12);
+    match action {
+        b"destroy" => {
+            CONTRACT_COMMON.destroy();
+        }
+        b"migrate" => {
+            let (code, vm_type, name, version, author, email, desc) = source.read().unwrap();
+            sink.write(CONTRACT_COMMON.migrate(code, vm_type, name, version, author, email, desc));
+        }
+        b"register" => {
+            let (marketplace, admin, endpoint, fee_rate) = source.read().unwrap();
+            sink.write(register(marketplace, admin, endpoint, fee_rate));
+        }
+        b"update" => {
+            let (marketplace, new_admin, endpoint, fee_rate) = source.read().unwrap();
+            sink.write(update(marketplace, new_admin, endpoint, fee_rate));
+        }
+        b"deregister" => {
+            let marketplace = source.read().unwrap();
+            sink.write(deregister(marketplace));
+        }
+        b"isRegistered" => {
+            let marketplace = source.read().unwrap();
+            sink.write(is_registered(marketplace));
+        }
+        b"getMarketplace" => {
+            let marketplace = source.read().unwrap();
+            sink.write(get_marketplace(marketplace));
+        }
+        b"getMarketplaceCount" => {
+            sink.write(get_marketplace_count());
+        }
+        b"getMarketplaces" => {
+            let (start, limit) = source.read().unwrap();
+            sink.write(get_marketplaces(start, limit));
+        }
+        _ => {
+            let method = str::from_utf8(action).ok().unwrap();
+            panic!("registry contract not support method:{}", method)
+        }
+    }
+    ret(sink.bytes());
+}
@@ -0,0 +1,43 @@
+use super::ostd::mock::build_runtime;
+use super::*;
+use ontio_std::types::Address;
+
+#[test]
+fn test_register() {
+    let handle = build_runtime();
+    let mp1 = Address::repeat_byte(1);
+    let mp2 = Address::repeat_byte(2);
+    let mp3 = Address::repeat_byte(3);
+    let admin = Address::repeat_byte(4);
+    handle.witness(&[mp1.clone(), mp2.clone(), mp3.clone(), admin.clone()]);
+    assert!(register(&mp1, &admin, b"http://mp1", 100));
+    assert!(register(&mp2, &admin, b"http://mp2", 200));
+    assert!(register(&mp3, &admin, b"http://mp3", 300));
+    assert!(is_registered(&mp2));
+    assert_eq!(get_marketplace_count(), 3);
+
+    assert!(update(&mp2, &admin, b"http://mp2.new", 50));
+    let infos = get_marketplaces(1, 1);
+    assert_eq!(infos.len(), 1);
+    assert_eq!(infos[0].endpoint, b"http://mp2.new".to_vec());
+    assert_eq!(infos[0].fee_rate, 50);
+
+    assert!(deregister(&mp1));
+    assert!(!is_registered(&mp1));
+    assert_eq!(get_marketplace_count(), 2);
+    let infos = get_marketplaces(0, 10);
+    assert_eq!(infos.len(), 2);
+    assert_eq!(infos[0].marketplace, mp3);
+    assert_eq!(infos[1].marketplace, mp2);
+    assert!(get_marketplace(&mp1).is_empty());
+}
+
+#[test]
+#[should_panic]
+fn test_register_without_marketplace_witness() {
+    let handle = build_runtime();
+    let mp = Address::repeat_byte(1);
+    let admin = Address::repeat_byte(4);
+    handle.witness(&[admin.clone()]);
+    register(&mp, &admin, b"http://mp", 100);
+}
@@ -4,9 +4,9 @@ extern crate ontio_std as ostd;
 use ostd::abi::{EventBuilder, Sink, Source};
 use ostd::database;
 use ostd::prelude::*;
-use ostd::runtime::{address, check_witness, input, ret, storage_read};
+use ostd::runtime::{address, caller, check_witness, input, ret, storage_read};
 extern crate common;
-use common::{TokenType, CONTRACT_COMMON};
+use common::{Fee, TokenType, CONTRACT_COMMON};
 use ostd::abi::{Decoder, Encoder};
 use ostd::contract::{ong, ont, wasm};
 
@@ -15,6 +15,7 @@ mod test;
 
 const KEY_REGISTRY_PARM: &[u8] = b"01";
 const KEY_BALANCE: &[u8] = b"02";
+const KEY_TRUSTED_CALLER: &[u8] = b"03";
 
 #[derive(Encoder, Decoder, Clone)]
 pub struct AddrAmt {
@@ -52,6 +53,22 @@ impl RegisterParam {
 /// `param_bytes` is the serialization result of RegisterParam
 pub fn register(key: &[u8], param_bytes: &[u8]) -> bool {
     let param = RegisterParam::from_bytes(param_bytes);
+    assert!(param.addr_amt.iter().any(|aa| check_witness(&aa.to)));
+    register_inner(key, param_bytes, param)
+}
+
+/// register on behalf of a receiver who has signed the serialization result of RegisterParam off-chain,
+/// only a trusted contract can invoke this method, and it must have verified the signature of `receiver`
+///
+/// `receiver` must be one of the receivers of the RegisterParam
+pub fn register_signed(key: &[u8], param_bytes: &[u8], receiver: &Address) -> bool {
+    assert!(is_trusted_caller(&caller()));
+    let param = RegisterParam::from_bytes(param_bytes);
+    assert!(param.addr_amt.iter().any(|aa| &aa.to == receiver));
+    register_inner(key, param_bytes, param)
+}
+
+fn register_inner(key: &[u8], param_bytes: &[u8], param: RegisterParam) -> bool {
     let data = storage_read(key.as_ref()).map(|val: Vec<u8>| val);
     assert!(data.is_none());
     match param.token_type {
@@ -61,8 +78,6 @@ pub fn register(key: &[u8], param_bytes: &[u8]) -> bool {
         _ => {}
     }
 
-    assert!(param.addr_amt.iter().any(|aa| check_witness(&aa.to)));
-
     database::put(generate_registry_param_key(key), param);
     EventBuilder::new()
         .string("register")
@@ -72,6 +87,30 @@ pub fn register(key: &[u8], param_bytes: &[u8]) -> bool {
     true
 }
 
+/// trust or distrust a contract to register on behalf of the receivers, need the contract admin signature
+///
+/// a trusted contract such as a marketplace which verifies the off-chain signature of a receiver can register
+/// by [`register_signed`](fn.register_signed.html)
+pub fn set_trusted_caller(contract: &Address, trusted: bool) -> bool {
+    assert!(check_witness(CONTRACT_COMMON.admin()));
+    let key = generate_trusted_caller_key(contract);
+    if trusted {
+        database::put(key, true);
+    } else {
+        database::delete(key);
+    }
+    EventBuilder::new()
+        .string("setTrustedCaller")
+        .address(contract)
+        .bool(trusted)
+        .notify();
+    true
+}
+
+pub fn is_trusted_caller(contract: &Address) -> bool {
+    database::get::<_, bool>(generate_trusted_caller_key(contract)).unwrap_or(false)
+}
+
 /// query RegisterParam by key
 pub fn get_register_param(key: &[u8]) -> RegisterParam {
     database::get::<_, RegisterParam>(generate_registry_param_key(key))
@@ -146,6 +185,67 @@ pub fn withdraw(key: &[u8], addr: &Address) -> bool {
 
 //mp invoke
 pub fn transfer_withdraw(from: &Address, key: &[u8], amt: U128) -> bool {
+    let rp = get_register_param(key);
+    transfer_withdraw_inner(from, key, amt, &rp.token_type, rp.contract_addr)
+}
+
+/// same as `transfer_withdraw`, but the token is specified by `fee` instead of the registered token type,
+/// used by items which accept more than one currency
+pub fn transfer_withdraw_fee(from: &Address, key: &[u8], amt: U128, fee: Fee) -> bool {
+    transfer_withdraw_inner(from, key, amt, &fee.contract_type, Some(fee.contract_addr))
+}
+
+/// pay the fees of several keys at once, the shares of the same address are added up and transferred once
+///
+/// the receivers are paid the same way as `transfer_withdraw` for every key in turn, the receivers who have
+/// withdrawn are skipped and the others are marked as withdrawn
+///
+/// `keys` and `amts` must have the same length, the token is specified by `fee`
+pub fn transfer_withdraw_multi(from: &Address, keys: &[Vec<u8>], amts: &[U128], fee: Fee) -> bool {
+    assert_eq!(keys.len(), amts.len());
+    let mut payouts: Vec<(Address, U128)> = vec![];
+    for (key, &amt) in keys.iter().zip(amts.iter()) {
+        let mut rp = get_register_param(key);
+        let total: u32 = rp.addr_amt.iter().map(|v| v.weight).sum();
+        for addr_amt in rp.addr_amt.iter_mut() {
+            if addr_amt.has_withdraw {
+                continue;
+            }
+            let temp = amt.checked_mul(addr_amt.weight as U128).unwrap();
+            let temp = temp.checked_div(total as U128).unwrap();
+            match payouts.iter_mut().find(|(to, _)| to == &addr_amt.to) {
+                Some((_, sum)) => *sum = sum.checked_add(temp).unwrap(),
+                None => payouts.push((addr_amt.to.clone(), temp)),
+            }
+            addr_amt.has_withdraw = true;
+        }
+        database::put(generate_registry_param_key(key), rp);
+        EventBuilder::new()
+            .string("transferWithdraw")
+            .address(from)
+            .bytearray(key)
+            .number(amt)
+            .notify();
+    }
+    for (to, amt) in payouts.iter() {
+        assert!(transfer_inner(
+            from,
+            to,
+            *amt,
+            &fee.contract_type,
+            Some(fee.contract_addr.clone())
+        ));
+    }
+    true
+}
+
+fn transfer_withdraw_inner(
+    from: &Address,
+    key: &[u8],
+    amt: U128,
+    token_type: &TokenType,
+    contract: Option<Address>,
+) -> bool {
     let mut rp = get_register_param(key);
     let total: u32 = rp.addr_amt.iter().map(|v| v.weight).sum();
     for addr_amt in rp.addr_amt.iter_mut() {
@@ -156,8 +256,8 @@ pub fn transfer_withdraw(from: &Address, key: &[u8], amt: U128) -> bool {
                 from,
                 &addr_amt.to,
                 temp,
-                &rp.token_type,
-                rp.contract_addr
+                token_type,
+                contract.clone()
             ));
             addr_amt.has_withdraw = true;
         }
@@ -210,6 +310,10 @@ fn generate_balance_key(key: &[u8]) -> Vec<u8> {
     [KEY_BALANCE, key].concat()
 }
 
+fn generate_trusted_caller_key(contract: &Address) -> Vec<u8> {
+    [KEY_TRUSTED_CALLER, contract.as_ref()].concat()
+}
+
 #[no_mangle]
 pub fn invoke() {
     let input = input();
@@ -225,6 +329,18 @@ pub fn invoke() {
             let (key, param_bytes) = source.read().unwrap();
             sink.write(register(key, param_bytes));
         }
+        b"registerSigned" => {
+            let (key, param_bytes, receiver) = source.read().unwrap();
+            sink.write(register_signed(key, param_bytes, receiver));
+        }
+        b"setTrustedCaller" => {
+            let (contract, trusted) = source.read().unwrap();
+            sink.write(set_trusted_caller(contract, trusted));
+        }
+        b"isTrustedCaller" => {
+            let contract = source.read().unwrap();
+            sink.write(is_trusted_caller(contract));
+        }
         b"getRegisterParam" => {
             let key = source.read().unwrap();
             sink.write(get_register_param(key));
@@ -245,6 +361,20 @@ pub fn invoke() {
             let (from, key, amt): (Address, &[u8], U128) = source.read().unwrap();
             sink.write(transfer_withdraw(&from, key, amt));
         }
+        b"transferWithdrawMulti" => {
+            let (from, keys, amts, fee): (Address, Vec<Vec<u8>>, Vec<U128>, Fee) =
+                source.read().unwrap();
+            sink.write(transfer_withdraw_multi(
+                &from,
+                keys.as_slice(),
+                amts.as_slice(),
+                fee,
+            ));
+        }
+        b"transferWithdrawFee" => {
+            let (from, key, amt, fee): (Address, &[u8], U128, Fee) = source.read().unwrap();
+            sink.write(transfer_withdraw_fee(&from, key, amt, fee));
+        }
         _ => {
             let method = str::from_utf8(action).ok().unwrap();
             panic!("not support method:{}", method)
@@ -1,12 +1,14 @@
 use super::ostd::mock::build_runtime;
 use super::{
-    get_balance, get_register_param, register, transfer, transfer_withdraw, withdraw, AddrAmt,
-    RegisterParam,
+    get_balance, get_register_param, register, register_signed, set_trusted_caller, transfer,
+    transfer_withdraw, transfer_withdraw_multi, withdraw, AddrAmt, RegisterParam,
 };
-use common::TokenType;
+use common::{Fee, TokenType, CONTRACT_COMMON};
 use hexutil::read_hex;
 use ontio_std::abi::{Sink, Source};
-use ontio_std::types::Address;
+use ontio_std::types::{Address, U128};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 #[test]
 fn test_registry3() {
@@ -118,3 +120,143 @@ fn test() {
     let rp = get_register_param(key);
     assert!(rp.addr_amt[0].has_withdraw);
 }
+
+#[test]
+fn transfer_withdraw_multi_test() {
+    let addr1 = Address::repeat_byte(1);
+    let addr2 = Address::repeat_byte(2);
+    let rp = RegisterParam {
+        addr_amt: vec![
+            AddrAmt {
+                to: addr1.clone(),
+                weight: 1000,
+                has_withdraw: false,
+            },
+            AddrAmt {
+                to: addr2.clone(),
+                weight: 9000,
+                has_withdraw: false,
+            },
+        ],
+        token_type: TokenType::ONG,
+        contract_addr: None,
+    };
+    let mut sink = Sink::new(64);
+    sink.write(rp);
+    let key = b"01".to_vec();
+    let key2 = b"02".to_vec();
+
+    let handle = build_runtime();
+    handle.witness(&[addr1.clone(), addr2.clone()]);
+    assert!(register(key.as_slice(), sink.bytes()));
+    assert!(register(
+        key2.as_slice(),
+        single_receiver_param(&addr2).as_slice()
+    ));
+
+    // the oep4 transfers made by the split contract
+    let transfers: Rc<RefCell<Vec<(Address, U128)>>> = Rc::default();
+    let recorded = transfers.clone();
+    handle.on_contract_call(move |_addr: &Address, data: &[u8]| -> Option<Vec<u8>> {
+        let mut source = Source::new(data);
+        let _method: &[u8] = source.read().unwrap();
+        let (_from, to, amt): (Address, Address, U128) = source.read().unwrap();
+        recorded.borrow_mut().push((to, amt));
+        Some(vec![1u8])
+    });
+    let from = Address::repeat_byte(4);
+    handle.witness(&[from.clone()]);
+    let fee = Fee {
+        contract_addr: Address::repeat_byte(3),
+        contract_type: TokenType::OEP4,
+        count: 0,
+    };
+    // the shares of addr2 in both items are transferred once
+    assert!(transfer_withdraw_multi(
+        &from,
+        &[key.clone(), key2.clone()],
+        &[100, 200],
+        fee.clone()
+    ));
+    assert_eq!(
+        *transfers.borrow(),
+        vec![(addr1.clone(), 10), (addr2.clone(), 290)]
+    );
+    let rp = get_register_param(key.as_slice());
+    assert!(rp.addr_amt[0].has_withdraw && rp.addr_amt[1].has_withdraw);
+    assert!(get_register_param(key2.as_slice()).addr_amt[0].has_withdraw);
+
+    // the receivers who have withdrawn are skipped, the same as `transfer_withdraw`
+    assert!(transfer_withdraw_multi(&from, &[key], &[1000], fee));
+    assert_eq!(transfers.borrow().len(), 2);
+}
+
+#[test]
+fn register_signed_test() {
+    let handle = build_runtime();
+    let marketplace = Address::repeat_byte(5);
+    let seller = Address::repeat_byte(1);
+    handle.witness(&[CONTRACT_COMMON.admin().clone()]);
+    assert!(set_trusted_caller(&marketplace, true));
+    handle.witness(&[]);
+    handle.caller(&marketplace);
+    assert!(register_signed(
+        b"01",
+        single_receiver_param(&seller).as_slice(),
+        &seller
+    ));
+    assert_eq!(get_register_param(b"01").addr_amt[0].to, seller);
+}
+
+#[test]
+#[should_panic]
+fn register_signed_by_untrusted_caller_test() {
+    let handle = build_runtime();
+    let seller = Address::repeat_byte(1);
+    handle.caller(&Address::repeat_byte(5));
+    register_signed(b"01", single_receiver_param(&seller).as_slice(), &seller);
+}
+
+#[test]
+#[should_panic]
+fn register_signed_for_others_test() {
+    let handle = build_runtime();
+    let marketplace = Address::repeat_byte(5);
+    handle.witness(&[CONTRACT_COMMON.admin().clone()]);
+    assert!(set_trusted_caller(&marketplace, true));
+    handle.caller(&marketplace);
+    let seller = Address::repeat_byte(1);
+    let param = single_receiver_param(&Address::repeat_byte(2));
+    register_signed(b"01", param.as_slice(), &seller);
+}
+
+#[test]
+#[should_panic]
+fn register_without_receiver_test() {
+    let handle = build_runtime();
+    let marketplace = Address::repeat_byte(5);
+    handle.witness(&[CONTRACT_COMMON.admin().clone()]);
+    assert!(set_trusted_caller(&marketplace, true));
+    // a trusted caller still needs the signature of a receiver to register
+    handle.witness(&[]);
+    handle.caller(&marketplace);
+    register(
+        b"01",
+        single_receiver_param(&Address::repeat_byte(1)).as_slice(),
+    );
+}
+
+fn single_receiver_param(to: &Address) -> Vec<u8> {
+    let rp = RegisterParam {
+        addr_amt: vec![AddrAmt {
+            to: to.clone(),
+            weight: 1,
+            has_withdraw: false,
+        }],
+        token_type: TokenType::ONG,
+        contract_addr: None,
+    };
+    let mut sink = Sink::new(64);
+    sink.write(rp);
+    sink.bytes().to_vec()
+}